@@ -0,0 +1,50 @@
+use std::io::Read;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rlz::RlzCompressor;
+
+fn english_50_mb() -> (Vec<u8>, rlz::Dictionary) {
+    let mut e50 = std::fs::File::open("./data/english.50MB").unwrap();
+    let mut e50_bytes = Vec::new();
+    e50.read_to_end(&mut e50_bytes).unwrap();
+
+    let mut dict_builder = rlz::Dictionary::reservoir_builder(4, 1024, 16);
+    dict_builder.sample(&e50_bytes[..]);
+    let dict = dict_builder.finish();
+    (e50_bytes, dict)
+}
+
+pub fn decode_50_mb(c: &mut Criterion) {
+    let (e50_bytes, dict) = english_50_mb();
+
+    let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+
+    let start = 1024 * 1024 * 16;
+    let stop = start + (1024 * 1024);
+    let decode_sample = &e50_bytes[start..stop];
+
+    let mut encoded = Vec::new();
+    rlz_compressor.encode(decode_sample, &mut encoded).unwrap();
+
+    let mut output = Vec::with_capacity(decode_sample.len());
+    let mut output_slice = vec![0; decode_sample.len()];
+    let mut group = c.benchmark_group("english.50MB_4mb_dict");
+    group.throughput(Throughput::Bytes(decode_sample.len() as u64));
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            output.clear();
+            rlz_compressor.decode(&encoded, &mut output).unwrap()
+        })
+    });
+    group.bench_function("decode_into", |b| {
+        b.iter(|| {
+            rlz_compressor
+                .decode_into(&encoded, &mut output_slice)
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decode_50_mb);
+criterion_main!(benches);
@@ -1,9 +1,9 @@
 use std::io::Read;
 
 use criterion::{criterion_group, criterion_main, Criterion, Throughput};
-use rlz::RlzCompressor;
+use rlz::{ParsingStrategy, RlzCompressor};
 
-pub fn encode_50_mb(c: &mut Criterion) {
+fn english_50_mb() -> (Vec<u8>, rlz::Dictionary) {
     let mut e50 = std::fs::File::open("./data/english.50MB").unwrap();
     let mut e50_bytes = Vec::new();
     e50.read_to_end(&mut e50_bytes).unwrap();
@@ -11,6 +11,11 @@ pub fn encode_50_mb(c: &mut Criterion) {
     let mut dict_builder = rlz::Dictionary::reservoir_builder(4, 1024, 16);
     dict_builder.sample(&e50_bytes[..]);
     let dict = dict_builder.finish();
+    (e50_bytes, dict)
+}
+
+pub fn encode_50_mb(c: &mut Criterion) {
+    let (e50_bytes, dict) = english_50_mb();
 
     let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
 
@@ -27,5 +32,38 @@ pub fn encode_50_mb(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, encode_50_mb);
+pub fn parsing_strategies_50_mb(c: &mut Criterion) {
+    let (e50_bytes, dict) = english_50_mb();
+
+    let start = 1024 * 1024 * 16;
+    let stop = start + (1024 * 1024);
+    let encode_sample = &e50_bytes[start..stop];
+
+    let mut output = Vec::with_capacity(1024 * 1024 * 256);
+    let mut group = c.benchmark_group("english.50MB_4mb_dict_parsing");
+    group.throughput(Throughput::Bytes(encode_sample.len() as u64));
+    for strategy in [ParsingStrategy::Greedy, ParsingStrategy::Lazy] {
+        let rlz_compressor = RlzCompressor::builder()
+            .parsing_strategy(strategy)
+            .build_from_dict(dict.clone());
+
+        output.clear();
+        let encoded_len = rlz_compressor.encode(encode_sample, &mut output).unwrap();
+        println!(
+            "{strategy:?}: {} -> {encoded_len} bytes (ratio {:.3})",
+            encode_sample.len(),
+            encode_sample.len() as f64 / encoded_len as f64
+        );
+
+        group.bench_function(format!("encode_{strategy:?}"), |b| {
+            b.iter(|| {
+                output.clear();
+                rlz_compressor.encode(encode_sample, &mut output).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encode_50_mb, parsing_strategies_50_mb);
 criterion_main!(benches);
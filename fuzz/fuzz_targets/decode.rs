@@ -0,0 +1,12 @@
+#![no_main]
+//! Decoding arbitrary bytes must fail with an error instead of panicking
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some((compressor, encoded)) = rlz_fuzz::decode_input(data) {
+        let _ = compressor.decode(encoded, &mut Vec::new());
+        let _ = compressor.decode_range(encoded, encoded.len() / 2..encoded.len(), &mut Vec::new());
+        let _ = compressor.decode_into(encoded, &mut vec![0; 4 * encoded.len()]);
+    }
+});
@@ -0,0 +1,11 @@
+#![no_main]
+//! Loading a compressor from arbitrary bytes must fail with an error instead of panicking
+
+use libfuzzer_sys::fuzz_target;
+use rlz::RlzCompressor;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = RlzCompressor::load(data);
+    let _ = RlzCompressor::load_with_index(data);
+    let _ = RlzCompressor::load_mapped(bytes::Bytes::copy_from_slice(data));
+});
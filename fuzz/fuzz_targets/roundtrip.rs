@@ -0,0 +1,29 @@
+#![no_main]
+//! Encoding arbitrary text against an arbitrary dictionary must decode to the same text
+
+use libfuzzer_sys::fuzz_target;
+use rlz::{Dictionary, ParsingStrategy, RlzCompressor};
+
+fuzz_target!(|data: &[u8]| {
+    let Some((literal_threshold, dict, text)) = rlz_fuzz::roundtrip_input(data) else {
+        return;
+    };
+    for strategy in [
+        ParsingStrategy::Greedy,
+        ParsingStrategy::Lazy,
+        ParsingStrategy::Optimal,
+    ] {
+        let compressor = RlzCompressor::builder()
+            .literal_threshold(literal_threshold)
+            .parsing_strategy(strategy)
+            .build_from_dict(Dictionary::from(dict));
+
+        let mut encoded = Vec::new();
+        let encoded_len = compressor.encode(text, &mut encoded).unwrap();
+        assert_eq!(encoded_len, encoded.len());
+
+        let mut decoded = Vec::new();
+        compressor.decode(&encoded, &mut decoded).unwrap();
+        assert_eq!(decoded, text);
+    }
+});
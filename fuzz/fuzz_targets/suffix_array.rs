@@ -0,0 +1,23 @@
+#![no_main]
+//! Suffix array search with `refine_bounds` must find the same positions as a naive matcher
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((wide, pattern, text)) = rlz_fuzz::suffix_array_input(data) else {
+        return;
+    };
+    if pattern.is_empty() {
+        return;
+    }
+    let expected: Vec<usize> = text
+        .windows(pattern.len())
+        .enumerate()
+        .filter(|(_, window)| *window == pattern)
+        .map(|(position, _)| position)
+        .collect();
+    assert_eq!(
+        rlz::fuzzing::suffix_array_matches(text, pattern, wide),
+        expected
+    );
+});
@@ -0,0 +1,60 @@
+//! Writes the seed corpus to `fuzz/seeds/<target>`. The seeds include the cases recorded
+//! in `proptest-regressions/coder.txt` (empty literal, offset and length streams).
+//!
+//! Run from the `fuzz` directory with `cargo run --bin seed_corpus`.
+
+use std::path::Path;
+
+use rlz::{Dictionary, RlzCompressor};
+
+const TEXTS: &[&[u8]] = &[
+    // proptest-regressions/coder.txt: literals = [], offsets = [], lens = []
+    b"",
+    b"banana$aba",
+    b"xyz",
+    b"bandana$ananas$cabana$banana",
+];
+
+fn write_seed(target: &str, name: &str, data: &[u8]) -> std::io::Result<()> {
+    let dir = Path::new("seeds").join(target);
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join(name), data)
+}
+
+fn main() -> Result<(), rlz::Error> {
+    for (selector, compressor) in rlz_fuzz::compressors().iter().enumerate() {
+        for (text_id, text) in TEXTS.iter().enumerate() {
+            let mut seed = vec![selector as u8];
+            compressor.encode(*text, &mut seed)?;
+            write_seed("decode", &format!("coder{selector}-text{text_id}"), &seed)?;
+        }
+    }
+
+    for (text_id, text) in TEXTS.iter().enumerate() {
+        let mut seed = vec![3];
+        seed.extend_from_slice(&(rlz_fuzz::DICT.len() as u16).to_le_bytes());
+        seed.extend_from_slice(rlz_fuzz::DICT);
+        seed.extend_from_slice(text);
+        write_seed("roundtrip", &format!("text{text_id}"), &seed)?;
+
+        let pattern = &text[..text.len().min(3)];
+        for wide in [0u8, 1] {
+            let mut seed = vec![wide, pattern.len() as u8];
+            seed.extend_from_slice(pattern);
+            seed.extend_from_slice(rlz_fuzz::DICT);
+            write_seed("suffix_array", &format!("text{text_id}-wide{wide}"), &seed)?;
+        }
+    }
+
+    let compressor = RlzCompressor::builder().build_from_dict(Dictionary::from(rlz_fuzz::DICT));
+    let mut stored = Vec::new();
+    compressor.store(&mut stored)?;
+    write_seed("load", "store", &stored)?;
+    stored.clear();
+    compressor.store_with_index(&mut stored)?;
+    write_seed("load", "store_with_index", &stored)?;
+    stored.clear();
+    compressor.store_mapped(&mut stored)?;
+    write_seed("load", "store_mapped", &stored)?;
+    Ok(())
+}
@@ -0,0 +1,65 @@
+//! Input formats shared by the fuzz targets and the seed corpus generator
+
+use std::sync::OnceLock;
+
+use rlz::{coder, Dictionary, RlzCompressor};
+
+/// Dictionary of the compressors used by the `decode` target
+pub const DICT: &[u8] = b"banana$bandana$cabana$ananas";
+
+/// Compressors for the `decode` target, the first input byte selects one
+pub fn compressors() -> &'static [RlzCompressor] {
+    static COMPRESSORS: OnceLock<Vec<RlzCompressor>> = OnceLock::new();
+    COMPRESSORS.get_or_init(|| {
+        [
+            coder::Coder::default(),
+            coder::Coder::new(coder::Codec::raw()),
+            coder::Coder::new(coder::Codec::bitpack()),
+            coder::Coder::per_stream(
+                coder::Codec::lz4(),
+                coder::Codec::vbyte(),
+                coder::Codec::vbyte_zstd(3),
+            ),
+            coder::Coder::default().checksums(true),
+        ]
+        .into_iter()
+        .map(|factor_coder| {
+            RlzCompressor::builder()
+                .factor_coder(factor_coder)
+                .build_from_dict(Dictionary::from(DICT))
+        })
+        .collect()
+    })
+}
+
+/// Split `data` into the selected compressor and the encoded document
+pub fn decode_input(data: &[u8]) -> Option<(&'static RlzCompressor, &[u8])> {
+    let (selector, encoded) = data.split_first()?;
+    let compressors = compressors();
+    Some((
+        &compressors[*selector as usize % compressors.len()],
+        encoded,
+    ))
+}
+
+/// Round trip input: `[literal threshold: u8][dict len: u16 le][dict][text]`
+pub fn roundtrip_input(data: &[u8]) -> Option<(u32, &[u8], &[u8])> {
+    let (threshold, rest) = data.split_first()?;
+    let dict_len = u16::from_le_bytes(rest.get(..2)?.try_into().ok()?) as usize;
+    let rest = &rest[2..];
+    let dict_len = dict_len.min(rest.len());
+    Some((
+        u32::from(*threshold % 16),
+        &rest[..dict_len],
+        &rest[dict_len..],
+    ))
+}
+
+/// Suffix array input: `[flags: u8][pattern len: u8][pattern][text]`. Bit 0 of flags
+/// selects the wide suffix array.
+pub fn suffix_array_input(data: &[u8]) -> Option<(bool, &[u8], &[u8])> {
+    let (&flags, rest) = data.split_first()?;
+    let (&pattern_len, rest) = rest.split_first()?;
+    let pattern_len = (pattern_len as usize).min(rest.len());
+    Some((flags & 1 == 1, &rest[..pattern_len], &rest[pattern_len..]))
+}
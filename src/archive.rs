@@ -0,0 +1,307 @@
+//! An archive stores many documents encoded against the same [`Dictionary`](crate::Dictionary)
+//! and allows retrieving a single document without decoding the rest.
+//!
+//! Documents are grouped into blocks. All documents of a block share one set of
+//! literal, offset and length streams, so larger blocks amortise the cost of compressing
+//! the streams over more documents at the price of decoding the whole block to retrieve
+//! a single document. The on-disk layout is
+//!
+//! ```text
+//! [block 0]...[block m-1][block offsets: (m + 1) x u64][first doc ids: (m + 1) x u64]
+//! [dict fingerprint: u64][config fingerprint: u32][directory checksum: u32][m: u64][n: u64][magic]
+//! ```
+//!
+//! where `n` is the number of documents, the fingerprints identify the dictionary and the
+//! settings the archive was written with and the checksum covers the block offsets and
+//! first doc ids. Each block is
+//!
+//! ```text
+//! [vbyte: docs in block][vbyte: factors of each doc]...[encoded factor streams]
+//! ```
+//!
+//! All fixed width integers are stored little endian. The encoded factor streams carry
+//! checksums if the compressor was built with [`RlzBuilder::checksums`](crate::RlzBuilder::checksums).
+//!
+//! ```rust
+//! use rlz::archive::{ArchiveReader, ArchiveWriter, BlockSize};
+//! use rlz::{Dictionary, RlzCompressor};
+//!
+//! let dict = Dictionary::from(&b"banana"[..]);
+//! let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+//!
+//! let mut writer = ArchiveWriter::new(&rlz_compressor, Vec::new())
+//!     .block_size(BlockSize::Documents(16));
+//! let first = writer.add(&b"banana$aba"[..]).unwrap();
+//! let second = writer.add(&b"ananas"[..]).unwrap();
+//! let archive = writer.finish().unwrap();
+//!
+//! let reader = ArchiveReader::new(&rlz_compressor, &archive[..]).unwrap();
+//! let mut recovered = Vec::new();
+//! reader.get(second, &mut recovered).unwrap();
+//! assert_eq!(recovered, b"ananas");
+//! ```
+mod reader;
+mod writer;
+
+pub use reader::ArchiveReader;
+pub use writer::ArchiveWriter;
+
+const MAGIC: &[u8; 4] = b"RLZA";
+const OFFSET_BYTES: usize = std::mem::size_of::<u64>();
+const FOOTER_BYTES: usize = 8 + 4 + 4 + 2 * OFFSET_BYTES + MAGIC.len();
+
+/// Determines when a block of documents is complete and gets encoded
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BlockSize {
+    /// Encode a block after this many documents were added
+    Documents(usize),
+    /// Encode a block once the documents added to it exceed this many uncompressed bytes
+    Bytes(usize),
+}
+
+impl BlockSize {
+    fn is_full(self, num_docs: usize, num_bytes: usize) -> bool {
+        match self {
+            BlockSize::Documents(docs) => num_docs >= docs,
+            BlockSize::Bytes(bytes) => num_bytes >= bytes,
+        }
+    }
+}
+
+impl Default for BlockSize {
+    fn default() -> BlockSize {
+        BlockSize::Documents(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ChecksumPart, Dictionary, Error, RlzCompressor};
+    use proptest::prelude::*;
+
+    fn block_size() -> impl Strategy<Value = BlockSize> {
+        prop_oneof![
+            (0..8usize).prop_map(BlockSize::Documents),
+            (0..256usize).prop_map(BlockSize::Bytes),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn write_and_get(dict: Vec<u8>, docs: Vec<Vec<u8>>, block_size in block_size()) {
+            let dict = Dictionary::from(&dict[..]);
+            let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+
+            let mut writer = ArchiveWriter::new(&rlz_compressor, Vec::new()).block_size(block_size);
+            for (expected_id, doc) in docs.iter().enumerate() {
+                let doc_id = writer.add(&doc[..])?;
+                assert_eq!(doc_id, expected_id);
+            }
+            let archive = writer.finish()?;
+
+            let reader = ArchiveReader::new(&rlz_compressor, &archive[..])?;
+            assert_eq!(reader.len(), docs.len());
+            for (doc_id, doc) in docs.iter().enumerate().rev() {
+                let mut recovered = Vec::new();
+                reader.get(doc_id, &mut recovered)?;
+                assert_eq!(&recovered, doc);
+            }
+            assert!(reader.get(docs.len(), Vec::new()).is_err());
+        }
+    }
+
+    #[test]
+    fn reject_truncated_archive() {
+        let dict = Dictionary::from(&b"banana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+
+        let mut writer = ArchiveWriter::new(&rlz_compressor, Vec::new());
+        writer.add(&b"banana$aba"[..]).unwrap();
+        let archive = writer.finish().unwrap();
+
+        for len in 0..archive.len() {
+            assert!(ArchiveReader::new(&rlz_compressor, &archive[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn reject_other_compressor() {
+        let dict = Dictionary::from(&b"banana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let mut writer = ArchiveWriter::new(&rlz_compressor, Vec::new());
+        writer.add(&b"banana$aba"[..]).unwrap();
+        let archive = writer.finish().unwrap();
+
+        let other_dict = RlzCompressor::builder().build_from_dict(Dictionary::from(&b"ananas"[..]));
+        let result = ArchiveReader::new(&other_dict, &archive[..]);
+        assert!(matches!(result, Err(Error::DictionaryMismatch { .. })));
+
+        let other_threshold = RlzCompressor::builder()
+            .literal_threshold(5)
+            .build_from_dict(Dictionary::from(&b"banana"[..]));
+        let result = ArchiveReader::new(&other_threshold, &archive[..]);
+        assert!(matches!(result, Err(Error::ConfigurationMismatch)));
+    }
+
+    #[test]
+    fn detect_corrupt_directory() {
+        let dict = Dictionary::from(&b"banana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let mut writer = ArchiveWriter::new(&rlz_compressor, Vec::new());
+        writer.add(&b"banana$aba"[..]).unwrap();
+        writer.add(&b"ananas"[..]).unwrap();
+        let mut archive = writer.finish().unwrap();
+
+        // last byte of the first doc id of the second block, the second to last directory entry
+        let first_doc = archive.len() - FOOTER_BYTES - OFFSET_BYTES - 1;
+        archive[first_doc] ^= 1;
+        let result = ArchiveReader::new(&rlz_compressor, &archive[..]);
+        assert!(matches!(
+            result,
+            Err(Error::ChecksumMismatch {
+                part: ChecksumPart::ArchiveDirectory
+            })
+        ));
+    }
+
+    /// Append a directory of `block_offsets` and `block_first_docs` with a matching checksum
+    /// and the footer to `blocks`
+    fn with_directory(
+        rlz_compressor: &RlzCompressor,
+        blocks: &[u8],
+        block_offsets: &[u64],
+        block_first_docs: &[u64],
+    ) -> Vec<u8> {
+        let mut directory = Vec::new();
+        for entry in block_offsets.iter().chain(block_first_docs) {
+            directory.extend_from_slice(&entry.to_le_bytes());
+        }
+        let num_blocks = block_offsets.len() as u64 - 1;
+        let num_docs = block_first_docs[block_first_docs.len() - 1];
+        let mut archive = blocks.to_vec();
+        archive.extend_from_slice(&directory);
+        archive.extend_from_slice(&rlz_compressor.fingerprint.dict.to_le_bytes());
+        archive.extend_from_slice(&rlz_compressor.fingerprint.config.to_le_bytes());
+        archive.extend_from_slice(&crate::frame::checksum(&directory).to_le_bytes());
+        archive.extend_from_slice(&num_blocks.to_le_bytes());
+        archive.extend_from_slice(&num_docs.to_le_bytes());
+        archive.extend_from_slice(MAGIC);
+        archive
+    }
+
+    #[test]
+    fn reject_inconsistent_directory() {
+        let dict = Dictionary::from(&b"banana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let mut writer = ArchiveWriter::new(&rlz_compressor, Vec::new());
+        writer.add(&b"banana$aba"[..]).unwrap();
+        writer.add(&b"ananas"[..]).unwrap();
+        let archive = writer.finish().unwrap();
+
+        let blocks_end = archive.len() - FOOTER_BYTES - 6 * OFFSET_BYTES;
+        let blocks = &archive[..blocks_end];
+        let second_block = u64::from_le_bytes(
+            archive[blocks_end + OFFSET_BYTES..][..OFFSET_BYTES]
+                .try_into()
+                .unwrap(),
+        );
+        let blocks_end = blocks_end as u64;
+        let rebuilt = with_directory(
+            &rlz_compressor,
+            blocks,
+            &[0, second_block, blocks_end],
+            &[0, 1, 2],
+        );
+        assert_eq!(rebuilt, archive);
+
+        let directories = [
+            // documents without any block
+            (vec![blocks_end], vec![2]),
+            // blocks do not start at the first byte or document
+            (vec![second_block, blocks_end], vec![0, 2]),
+            (vec![0, blocks_end], vec![1, 2]),
+            // empty blocks
+            (vec![0, second_block, blocks_end], vec![0, 0, 2]),
+            (vec![0, 0, blocks_end], vec![0, 1, 2]),
+        ];
+        for (block_offsets, block_first_docs) in directories {
+            let crafted =
+                with_directory(&rlz_compressor, blocks, &block_offsets, &block_first_docs);
+            let result = ArchiveReader::new(&rlz_compressor, &crafted[..]);
+            assert!(matches!(result, Err(Error::InvalidArchive)));
+        }
+    }
+
+    #[test]
+    fn reject_factor_count_beyond_length_stream() {
+        let dict = Dictionary::from(&b"banana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let mut writer =
+            ArchiveWriter::new(&rlz_compressor, Vec::new()).block_size(BlockSize::Documents(1));
+        writer.add(&b"banana$aba"[..]).unwrap();
+        writer.add(&b"ananas"[..]).unwrap();
+        let mut archive = writer.finish().unwrap();
+
+        // the block header is [docs in block][factors of the document], both single byte vbytes
+        let blocks_end = archive.len() - FOOTER_BYTES - 6 * OFFSET_BYTES;
+        let second_block = u64::from_le_bytes(
+            archive[blocks_end + OFFSET_BYTES..][..OFFSET_BYTES]
+                .try_into()
+                .unwrap(),
+        );
+        archive[usize::try_from(second_block).unwrap() + 1] += 1;
+
+        // the corrupt block is only detected when one of its documents is retrieved
+        let reader = ArchiveReader::new(&rlz_compressor, &archive[..]).unwrap();
+        let mut first = Vec::new();
+        reader.get(0, &mut first).unwrap();
+        assert_eq!(first, b"banana$aba");
+        let result = reader.get(1, Vec::new());
+        assert!(matches!(result, Err(Error::InvalidArchive)));
+    }
+
+    #[test]
+    fn skip_past_length_stream() {
+        let dict = Dictionary::from(&b"banana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let mut output = Vec::new();
+        rlz_compressor
+            .encode(&b"banana$aba"[..], &mut output)
+            .unwrap();
+        let num_factors = rlz_compressor.factor_lens(&output).unwrap().len();
+
+        let mut streams = &output[..];
+        crate::frame::FrameHeader::read(&mut streams).unwrap();
+        let result = rlz_compressor.decoder.decode_factors(
+            &rlz_compressor.dict,
+            streams,
+            num_factors,
+            num_factors + 1,
+            1,
+            Vec::new(),
+        );
+        assert!(matches!(
+            result,
+            Err(Error::TruncatedStream {
+                stream: crate::coder::Stream::Lens
+            })
+        ));
+    }
+
+    #[test]
+    fn blocks_share_streams() {
+        let dict = Dictionary::from(&b"the quick brown fox jumps over the lazy dog"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let docs = vec![&b"the lazy fox jumps over the quick brown dog"[..]; 64];
+
+        let archive_len = |block_size| {
+            let mut writer = ArchiveWriter::new(&rlz_compressor, Vec::new()).block_size(block_size);
+            for doc in &docs {
+                writer.add(*doc).unwrap();
+            }
+            writer.finish().unwrap().len()
+        };
+        assert!(archive_len(BlockSize::Documents(64)) < archive_len(BlockSize::Documents(1)));
+    }
+}
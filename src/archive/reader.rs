@@ -0,0 +1,142 @@
+use bytes::Buf;
+
+use crate::{frame, ChecksumPart, Error, RlzCompressor};
+
+use super::{FOOTER_BYTES, MAGIC, OFFSET_BYTES};
+
+/// Retrieves individual documents from an archive created by an [`ArchiveWriter`](super::ArchiveWriter)
+#[allow(clippy::module_name_repetitions)]
+pub struct ArchiveReader<'c, D: AsRef<[u8]>> {
+    compressor: &'c RlzCompressor,
+    data: D,
+    block_offsets: Vec<usize>,
+    block_first_docs: Vec<usize>,
+}
+
+impl<'c, D: AsRef<[u8]>> ArchiveReader<'c, D> {
+    /// Open the archive stored in `data`. `compressor` has to use the same dictionary and
+    /// settings as the compressor the archive was written with. Only the directory is
+    /// checked, blocks are checked when their documents are retrieved.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `data` does not contain a valid archive or the archive was written with
+    /// a different dictionary or settings
+    #[tracing::instrument(skip_all)]
+    pub fn new(compressor: &'c RlzCompressor, data: D) -> Result<Self, Error> {
+        let bytes = data.as_ref();
+        let footer_start = bytes
+            .len()
+            .checked_sub(FOOTER_BYTES)
+            .ok_or(Error::InvalidArchive)?;
+        let mut footer = &bytes[footer_start..];
+        let fingerprint = frame::Fingerprint {
+            dict: footer.get_u64_le(),
+            config: footer.get_u32_le(),
+        };
+        let directory_checksum = footer.get_u32_le();
+        let num_blocks = read_usize(&mut footer)?;
+        let num_docs = read_usize(&mut footer)?;
+        if footer != MAGIC {
+            return Err(Error::InvalidArchive);
+        }
+        fingerprint.check(compressor.fingerprint)?;
+
+        let directory_start = num_blocks
+            .checked_add(1)
+            .and_then(|entries| entries.checked_mul(2 * OFFSET_BYTES))
+            .and_then(|directory_bytes| footer_start.checked_sub(directory_bytes))
+            .ok_or(Error::InvalidArchive)?;
+        let mut directory = &bytes[directory_start..footer_start];
+        if frame::checksum(directory) != directory_checksum {
+            return Err(Error::ChecksumMismatch {
+                part: ChecksumPart::ArchiveDirectory,
+            });
+        }
+        let block_offsets = (0..=num_blocks)
+            .map(|_| read_usize(&mut directory))
+            .collect::<Result<Vec<_>, _>>()?;
+        let block_first_docs = (0..=num_blocks)
+            .map(|_| read_usize(&mut directory))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // every block holds at least one document and occupies at least one byte
+        let is_increasing = |entries: &[usize]| entries.windows(2).all(|w| w[0] < w[1]);
+        if !is_increasing(&block_offsets)
+            || !is_increasing(&block_first_docs)
+            || block_offsets[0] != 0
+            || block_first_docs[0] != 0
+            || block_offsets[num_blocks] != directory_start
+            || block_first_docs[num_blocks] != num_docs
+        {
+            return Err(Error::InvalidArchive);
+        }
+
+        Ok(Self {
+            compressor,
+            data,
+            block_offsets,
+            block_first_docs,
+        })
+    }
+
+    /// Number of documents in the archive
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.block_first_docs[self.block_first_docs.len() - 1]
+    }
+
+    /// Returns true if the archive contains no documents
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Decode the document with id `doc_id` into `output`. Only the block containing
+    /// the document is decoded.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `doc_id` is not part of the archive, the document can not be decoded or
+    /// the factor counts of the documents in its block do not match the block's streams
+    #[tracing::instrument(skip_all)]
+    pub fn get(&self, doc_id: usize, output: impl std::io::Write) -> Result<usize, Error> {
+        if doc_id >= self.len() {
+            return Err(Error::DocumentNotFound { doc_id });
+        }
+        let block_id = self
+            .block_first_docs
+            .partition_point(|&first| first <= doc_id)
+            - 1;
+        let block =
+            &self.data.as_ref()[self.block_offsets[block_id]..self.block_offsets[block_id + 1]];
+        let docs_in_block = self.block_first_docs[block_id + 1] - self.block_first_docs[block_id];
+        let (streams, doc_factors) = read_block_header(block, docs_in_block)?;
+
+        let position = doc_id - self.block_first_docs[block_id];
+        self.compressor.decoder.decode_factors(
+            &self.compressor.dict,
+            streams,
+            doc_factors.iter().sum(),
+            doc_factors[..position].iter().sum(),
+            doc_factors[position],
+            output,
+        )
+    }
+}
+
+/// The factor counts of the `docs_in_block` documents of `block` and its encoded streams
+fn read_block_header(mut block: &[u8], docs_in_block: usize) -> Result<(&[u8], Vec<usize>), Error> {
+    // each factor count takes at least one byte
+    if docs_in_block > block.len() || crate::vbyte::decode(&mut block)? as usize != docs_in_block {
+        return Err(Error::InvalidArchive);
+    }
+    let doc_factors = (0..docs_in_block)
+        .map(|_| crate::vbyte::decode(&mut block).map(|num_factors| num_factors as usize))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((block, doc_factors))
+}
+
+fn read_usize(bytes: &mut &[u8]) -> Result<usize, Error> {
+    usize::try_from(bytes.get_u64_le()).map_err(|_| Error::InvalidArchive)
+}
@@ -0,0 +1,140 @@
+use bytes::Buf;
+use std::io::Write;
+
+use crate::{frame, scratch::Scratch, Error, RlzCompressor};
+
+use super::BlockSize;
+
+/// Appends documents encoded against the same dictionary to an archive
+#[allow(clippy::module_name_repetitions)]
+pub struct ArchiveWriter<'c, W: std::io::Write> {
+    compressor: &'c RlzCompressor,
+    output: W,
+    block_size: BlockSize,
+    scratch: Scratch,
+    block_factors: Vec<usize>,
+    block_bytes: usize,
+    block_offsets: Vec<u64>,
+    block_first_docs: Vec<u64>,
+    num_docs: usize,
+    buffer: Vec<u8>,
+}
+
+impl<'c, W: std::io::Write> ArchiveWriter<'c, W> {
+    /// Create a new archive which encodes documents with `compressor` and writes them to `output`
+    pub fn new(compressor: &'c RlzCompressor, output: W) -> Self {
+        Self {
+            compressor,
+            output,
+            block_size: BlockSize::default(),
+            scratch: Scratch::default(),
+            block_factors: Vec::new(),
+            block_bytes: 0,
+            block_offsets: vec![0],
+            block_first_docs: vec![0],
+            num_docs: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Specify how many documents are grouped into one block
+    #[must_use]
+    pub fn block_size(mut self, block_size: BlockSize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Encode a document, append it to the archive and return its document id
+    ///
+    /// # Errors
+    ///
+    /// Fails if the compressor can not encode or writing the encoded block fails
+    #[tracing::instrument(skip_all)]
+    pub fn add(&mut self, document: impl Buf) -> Result<usize, Error> {
+        let encoder = self
+            .compressor
+            .encoder
+            .as_ref()
+            .ok_or(Error::NoEncoderAvailable)?;
+        self.block_bytes += document.remaining();
+        let num_factors =
+            encoder.factorize_into(&self.compressor.dict, document, &mut self.scratch, None);
+        self.block_factors.push(num_factors);
+        let doc_id = self.num_docs;
+        self.num_docs += 1;
+
+        if self
+            .block_size
+            .is_full(self.block_factors.len(), self.block_bytes)
+        {
+            self.flush_block()?;
+        }
+        Ok(doc_id)
+    }
+
+    /// Number of documents in the archive
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.num_docs
+    }
+
+    /// Returns true if no documents were added to the archive
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.num_docs == 0
+    }
+
+    /// Encode the pending block (if any), write the directory and the fingerprint of the
+    /// compressor and return the underlying writer
+    ///
+    /// # Errors
+    ///
+    /// Fails if encoding the pending block or writing the directory fails
+    #[tracing::instrument(skip_all)]
+    pub fn finish(mut self) -> Result<W, Error> {
+        if !self.block_factors.is_empty() {
+            self.flush_block()?;
+        }
+        let mut directory = frame::ChecksumWriter::new(&mut self.output);
+        for offset in self.block_offsets.iter().chain(&self.block_first_docs) {
+            directory.write_all(&offset.to_le_bytes())?;
+        }
+        let directory_checksum = directory.checksum();
+        let fingerprint = self.compressor.fingerprint;
+        self.output.write_all(&fingerprint.dict.to_le_bytes())?;
+        self.output.write_all(&fingerprint.config.to_le_bytes())?;
+        self.output.write_all(&directory_checksum.to_le_bytes())?;
+        let num_blocks = self.block_offsets.len() as u64 - 1;
+        self.output.write_all(&num_blocks.to_le_bytes())?;
+        self.output
+            .write_all(&(self.num_docs as u64).to_le_bytes())?;
+        self.output.write_all(super::MAGIC)?;
+        self.output.flush()?;
+        Ok(self.output)
+    }
+
+    #[tracing::instrument(skip_all)]
+    #[allow(clippy::cast_possible_truncation)]
+    fn flush_block(&mut self) -> Result<(), Error> {
+        self.buffer.clear();
+        let mut header_bytes =
+            crate::vbyte::encode(&mut self.buffer, self.block_factors.len() as u32);
+        for num_factors in &self.block_factors {
+            header_bytes += crate::vbyte::encode(&mut self.buffer, *num_factors as u32);
+        }
+        let config = &self.compressor.config;
+        let offset_bytes = config.index_width.offset_bytes(self.compressor.dict.len());
+        let coder = &config.factor_compression;
+        let encoded_bytes = coder.encode(&mut self.buffer, &mut self.scratch, offset_bytes)?;
+        self.output.write_all(&self.buffer)?;
+
+        let block_start = self.block_offsets[self.block_offsets.len() - 1];
+        self.block_offsets
+            .push(block_start + (header_bytes + encoded_bytes) as u64);
+        self.block_first_docs.push(self.num_docs as u64);
+        self.block_factors.clear();
+        self.block_bytes = 0;
+        self.scratch.clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{scratch::Scratch, Error};
+
+/// Worker threads for [`RlzCompressor::encode_batch`](crate::RlzCompressor::encode_batch) and
+/// [`RlzCompressor::decode_batch`](crate::RlzCompressor::decode_batch). Create one pool and
+/// reuse it for all batches: the threads and the scratch space of each thread persist
+/// across batches.
+pub struct BatchPool {
+    pool: rayon::ThreadPool,
+    /// scratch space of each worker, indexed by the thread index within `pool`. Only the
+    /// worker itself locks its scratch space, so the locks are never contended.
+    scratch: Vec<Mutex<Scratch>>,
+}
+
+impl BatchPool {
+    /// Pool of `num_threads` worker threads, one per available CPU if `num_threads` is 0
+    ///
+    /// # Errors
+    ///
+    /// Fails if the worker threads can not be spawned
+    pub fn new(num_threads: usize) -> Result<BatchPool, Error> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .thread_name(|index| format!("rlz-batch-{index}"))
+            .build()?;
+        let scratch = (0..pool.current_num_threads())
+            .map(|_| Mutex::new(Scratch::default()))
+            .collect();
+        Ok(BatchPool { pool, scratch })
+    }
+
+    /// Number of worker threads
+    #[must_use]
+    pub fn num_threads(&self) -> usize {
+        self.scratch.len()
+    }
+
+    /// Apply `f` to all `items` on the workers of the pool and return the results in the
+    /// order of `items`. Each worker passes its own scratch space to `f` for all of its
+    /// items, so only taking the next item from the shared queue is synchronized.
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn map_ordered<I, T, F>(&self, items: I, f: F) -> Result<Vec<T>, Error>
+    where
+        I: Iterator + Send,
+        I::Item: Send,
+        T: Send,
+        F: Fn(I::Item, &mut Scratch) -> Result<T, Error> + Sync,
+    {
+        let queue = Mutex::new(items.enumerate());
+        let failed = AtomicBool::new(false);
+        let results = Mutex::new(Vec::new());
+        self.pool.scope(|scope| {
+            for _ in 0..self.num_threads() {
+                scope.spawn(|_| {
+                    // spawned tasks always run on a thread of `pool`
+                    let worker = rayon::current_thread_index().unwrap_or_default();
+                    let mut scratch = self.scratch[worker].lock();
+                    let mut worker_results = Vec::new();
+                    // stop taking items once any item failed
+                    while !failed.load(Ordering::Relaxed) {
+                        let Some((id, item)) = queue.lock().next() else {
+                            break;
+                        };
+                        let result = f(item, &mut scratch);
+                        failed.fetch_or(result.is_err(), Ordering::Relaxed);
+                        worker_results.push((id, result));
+                    }
+                    results.lock().append(&mut worker_results);
+                });
+            }
+        });
+        let mut results: Vec<(usize, Result<T, Error>)> = results.into_inner();
+        results.sort_unstable_by_key(|(id, _)| *id);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn keeps_order(items: Vec<u32>, num_threads in 1..8usize) {
+            let pool = BatchPool::new(num_threads)?;
+            assert_eq!(pool.num_threads(), num_threads);
+            let doubled = pool.map_ordered(items.iter(), |item, _| Ok(u64::from(*item) * 2))?;
+            let expected: Vec<u64> = items.iter().map(|item| u64::from(*item) * 2).collect();
+            assert_eq!(doubled, expected);
+        }
+    }
+
+    #[test]
+    fn stops_on_error() {
+        let pool = BatchPool::new(4).unwrap();
+        let result = pool.map_ordered(0..1000, |item, _| {
+            if item == 10 {
+                Err(Error::NoEncoderAvailable)
+            } else {
+                Ok(item)
+            }
+        });
+        assert!(matches!(result, Err(Error::NoEncoderAvailable)));
+    }
+
+    #[test]
+    fn reuses_scratch_across_batches() {
+        let pool = BatchPool::new(2).unwrap();
+        for _ in 0..2 {
+            pool.map_ordered(0..64u8, |item, scratch| {
+                scratch.literals.extend_from_slice(&[item]);
+                Ok(())
+            })
+            .unwrap();
+        }
+        // the workers kept their scratch space and the bytes appended by both batches
+        let appended: usize = pool.scratch.iter().map(|s| s.lock().literals.len()).sum();
+        assert_eq!(appended, 128);
+    }
+}
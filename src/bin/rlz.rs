@@ -0,0 +1,430 @@
+//! Command line tool to build dictionaries and to compress, decompress and inspect documents
+//! with compressors stored by [`RlzCompressor::store`]
+#![warn(clippy::pedantic)]
+
+use clap::builder::RangedU64ValueParser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rlz::stream::{self, RlzReader, RlzWriter};
+use rlz::{Dictionary, EncodeStats, ParsingStrategy, RlzCompressor};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Relative Lempel-Ziv compression against a fixed dictionary
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Sample a dictionary from files and store a compressor built from it
+    BuildDict(BuildDict),
+    /// Compress a file or stdin into a stream
+    Compress(Compress),
+    /// Decompress a stream written by `compress`
+    Decompress(Decompress),
+    /// Print the configuration, dictionary size and a factor histogram of a compressed file
+    Inspect(Inspect),
+    /// Measure encoding and decoding throughput on a file
+    Bench(Bench),
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Sampling {
+    /// Uniform reservoir sample over all files
+    Reservoir,
+    /// Separate reservoir per file
+    Stratified,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Parsing {
+    Greedy,
+    Lazy,
+    Optimal,
+}
+
+impl From<Parsing> for ParsingStrategy {
+    fn from(parsing: Parsing) -> Self {
+        match parsing {
+            Parsing::Greedy => ParsingStrategy::Greedy,
+            Parsing::Lazy => ParsingStrategy::Lazy,
+            Parsing::Optimal => ParsingStrategy::Optimal,
+        }
+    }
+}
+
+#[derive(Args)]
+struct BuildDict {
+    /// Files to sample the dictionary from
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+    /// Path of the stored compressor
+    #[arg(short, long)]
+    output: PathBuf,
+    /// How samples are drawn from the input files
+    #[arg(long, value_enum, default_value_t = Sampling::Reservoir)]
+    sampling: Sampling,
+    /// Dictionary size in MiB
+    #[arg(long, default_value_t = 16)]
+    dict_mib: usize,
+    /// Bytes per sample
+    #[arg(long, default_value_t = 1024, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    sample_size: usize,
+    /// Reservoir size in MiB for reservoir sampling
+    #[arg(long, default_value_t = 64, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    reservoir_mib: usize,
+    /// Samples kept per file for stratified sampling
+    #[arg(long, default_value_t = 1024, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    items_per_bucket: usize,
+    /// Seed for sampling, the same seed and inputs build the same dictionary
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Factors of at most this many bytes are stored as literals
+    #[arg(long, default_value_t = 3)]
+    literal_threshold: u32,
+    /// How documents are split into factors
+    #[arg(long, value_enum, default_value_t = Parsing::Greedy)]
+    parsing: Parsing,
+    /// Store stream and document checksums in compressed files
+    #[arg(long)]
+    checksums: bool,
+}
+
+#[derive(Args)]
+struct Compress {
+    /// Stored compressor
+    #[arg(short, long)]
+    compressor: PathBuf,
+    /// File to compress, stdin if omitted or `-`
+    input: Option<PathBuf>,
+    /// Compressed file, stdout if omitted or `-`
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Bytes of input encoded per block in KiB
+    #[arg(long, default_value_t = 1024)]
+    chunk_kib: usize,
+}
+
+#[derive(Args)]
+struct Decompress {
+    /// Stored compressor
+    #[arg(short, long)]
+    compressor: PathBuf,
+    /// Compressed file, stdin if omitted or `-`
+    input: Option<PathBuf>,
+    /// Decompressed file, stdout if omitted or `-`
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct Inspect {
+    /// Stored compressor
+    #[arg(short, long)]
+    compressor: PathBuf,
+    /// Compressed file written by `compress`
+    input: PathBuf,
+}
+
+#[derive(Args)]
+struct Bench {
+    /// Stored compressor
+    #[arg(short, long)]
+    compressor: PathBuf,
+    /// File to compress, split into documents of `--document-kib`
+    input: PathBuf,
+    /// Size of the documents the input is split into in KiB
+    #[arg(long, default_value_t = 64)]
+    document_kib: usize,
+    /// Number of times every document is encoded and decoded
+    #[arg(long, default_value_t = 5)]
+    iterations: usize,
+}
+
+fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::BuildDict(args) => build_dict(&args),
+        Command::Compress(args) => compress(&args),
+        Command::Decompress(args) => decompress(&args),
+        Command::Inspect(args) => inspect(&args),
+        Command::Bench(args) => bench(&args),
+    }
+}
+
+fn open_input(path: Option<&Path>) -> Result<Box<dyn Read>> {
+    Ok(match path {
+        Some(path) if path != Path::new("-") => Box::new(BufReader::new(File::open(path)?)),
+        _ => Box::new(io::stdin().lock()),
+    })
+}
+
+fn create_output(path: Option<&Path>) -> Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) if path != Path::new("-") => Box::new(BufWriter::new(File::create(path)?)),
+        _ => Box::new(BufWriter::new(io::stdout().lock())),
+    })
+}
+
+fn load_compressor(path: &Path) -> Result<RlzCompressor> {
+    Ok(RlzCompressor::load(BufReader::new(File::open(path)?))?)
+}
+
+/// Feed `file` to `sample` in buffers holding a whole number of samples
+fn sample_file(path: &Path, sample_size: usize, mut sample: impl FnMut(&[u8])) -> Result<()> {
+    let mut file = File::open(path)?;
+    let mut buffer = vec![0; sample_size.max(1) * 1024];
+    loop {
+        let mut len = 0;
+        while len < buffer.len() {
+            match file.read(&mut buffer[len..])? {
+                0 => break,
+                read => len += read,
+            }
+        }
+        if len == 0 {
+            return Ok(());
+        }
+        sample(&buffer[..len]);
+    }
+}
+
+fn build_dict(args: &BuildDict) -> Result<()> {
+    let dict = match args.sampling {
+        Sampling::Reservoir => {
+            if args.sample_size > args.reservoir_mib * 1024 * 1024 {
+                return Err("--sample-size must not exceed the reservoir size".into());
+            }
+            let mut builder =
+                Dictionary::reservoir_builder(args.dict_mib, args.sample_size, args.reservoir_mib);
+            if let Some(seed) = args.seed {
+                builder = builder.seed(seed);
+            }
+            for input in &args.inputs {
+                sample_file(input, args.sample_size, |bytes| builder.sample(bytes))?;
+            }
+            builder.finish()
+        }
+        Sampling::Stratified => {
+            let mut builder = Dictionary::stratified_reservoir_builder(
+                args.dict_mib,
+                args.sample_size,
+                args.items_per_bucket,
+            );
+            if let Some(seed) = args.seed {
+                builder = builder.seed(seed);
+            }
+            for input in &args.inputs {
+                sample_file(input, args.sample_size, |bytes| {
+                    builder.sample(input, bytes);
+                })?;
+            }
+            builder.finish()
+        }
+    };
+    eprintln!("sampled dictionary of {} bytes", dict.len());
+
+    let compressor = RlzCompressor::builder()
+        .literal_threshold(args.literal_threshold)
+        .parsing_strategy(args.parsing.into())
+        .checksums(args.checksums)
+        .build_decoder_from_dict(dict);
+    let mut output = BufWriter::new(File::create(&args.output)?);
+    compressor.store(&mut output)?;
+    output.flush()?;
+    Ok(())
+}
+
+fn compress(args: &Compress) -> Result<()> {
+    let mut compressor = load_compressor(&args.compressor)?;
+    compressor.enable_encode();
+    let mut input = open_input(args.input.as_deref())?;
+    let output = create_output(args.output.as_deref())?;
+
+    let mut writer = RlzWriter::new(&compressor, output).chunk_size(args.chunk_kib * 1024);
+    io::copy(&mut input, &mut writer)?;
+    writer.finish()?.flush()?;
+    Ok(())
+}
+
+fn decompress(args: &Decompress) -> Result<()> {
+    let compressor = load_compressor(&args.compressor)?;
+    let input = open_input(args.input.as_deref())?;
+    let mut output = create_output(args.output.as_deref())?;
+
+    io::copy(&mut RlzReader::new(&compressor, input), &mut output)?;
+    output.flush()?;
+    Ok(())
+}
+
+fn inspect(args: &Inspect) -> Result<()> {
+    let compressor = load_compressor(&args.compressor)?;
+    let encoded = std::fs::read(&args.input)?;
+
+    let mut num_blocks = 0;
+    let mut stats = EncodeStats::default();
+    for block in stream::blocks(&encoded) {
+        let block = block?;
+        num_blocks += 1;
+        stats.add_sizes(compressor.stream_sizes(block)?);
+        for len in compressor.factor_lens(block)? {
+            let literal = len <= compressor.config().literal_threshold;
+            stats.add_factor_len(len as usize, literal);
+        }
+    }
+    let sizes = stats.stream_sizes;
+
+    let mut out = io::stdout().lock();
+    writeln!(out, "configuration: {:#?}", compressor.config())?;
+    writeln!(
+        out,
+        "dictionary: {} bytes, fingerprint {:#018x}",
+        compressor.dictionary().len(),
+        compressor.dictionary().fingerprint()
+    )?;
+    writeln!(
+        out,
+        "blocks: {num_blocks}, decoded: {} bytes, encoded: {} bytes, ratio {:.3}",
+        stats.decoded_bytes,
+        encoded.len(),
+        ratio(stats.decoded_bytes, encoded.len())
+    )?;
+    writeln!(
+        out,
+        "streams: header {} literals {} offsets {} lens {} bytes",
+        sizes.header, sizes.literals, sizes.offsets, sizes.lens
+    )?;
+    writeln!(
+        out,
+        "factors: {} ({} copies, {} literals), average length {:.2}",
+        stats.factors(),
+        stats.copy_factors,
+        stats.literal_factors,
+        stats.average_factor_len()
+    )?;
+    writeln!(out, "factor lengths:")?;
+    for (bucket, count) in stats
+        .len_histogram
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+    {
+        let (start, end) = match bucket {
+            0 => (0, 1),
+            bucket => (1u64 << (bucket - 1), 1u64 << bucket),
+        };
+        writeln!(out, "  {start:>10}..{end:<10} {count}")?;
+    }
+    Ok(())
+}
+
+fn bench(args: &Bench) -> Result<()> {
+    let mut compressor = load_compressor(&args.compressor)?;
+    compressor.enable_encode();
+    let input = std::fs::read(&args.input)?;
+    let documents: Vec<&[u8]> = input.chunks((args.document_kib * 1024).max(1)).collect();
+
+    let mut encoded = Vec::with_capacity(documents.len());
+    let mut encode_time = Duration::ZERO;
+    let mut decode_time = Duration::ZERO;
+    let mut decoded = Vec::new();
+    for _ in 0..args.iterations.max(1) {
+        encoded.clear();
+        let start = Instant::now();
+        for document in &documents {
+            let mut output = Vec::new();
+            compressor.encode(*document, &mut output)?;
+            encoded.push(output);
+        }
+        encode_time += start.elapsed();
+
+        let start = Instant::now();
+        for document in &encoded {
+            decoded.clear();
+            compressor.decode(document, &mut decoded)?;
+        }
+        decode_time += start.elapsed();
+    }
+
+    let encoded_len: usize = encoded.iter().map(Vec::len).sum();
+    let processed = ratio(input.len() * args.iterations.max(1), 1024 * 1024);
+    println!(
+        "{} documents, {} -> {encoded_len} bytes, ratio {:.3}",
+        documents.len(),
+        input.len(),
+        ratio(input.len(), encoded_len)
+    );
+    println!("encode: {:.1} MiB/s", processed / encode_time.as_secs_f64());
+    println!("decode: {:.1} MiB/s", processed / decode_time.as_secs_f64());
+    Ok(())
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    numerator as f64 / denominator.max(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_dict_compress_and_decompress() {
+        let dir = std::env::temp_dir().join(format!("rlz-cli-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let text: Vec<u8> = (0..2000u32)
+            .flat_map(|i| format!("document {} of the rlz round trip\n", i % 97).into_bytes())
+            .collect();
+        let input = dir.join("input");
+        std::fs::write(&input, &text).unwrap();
+
+        let compressor = dir.join("compressor");
+        build_dict(&BuildDict {
+            inputs: vec![input.clone()],
+            output: compressor.clone(),
+            sampling: Sampling::Reservoir,
+            dict_mib: 1,
+            sample_size: 64,
+            reservoir_mib: 1,
+            items_per_bucket: 1024,
+            seed: Some(7),
+            literal_threshold: 3,
+            parsing: Parsing::Greedy,
+            checksums: true,
+        })
+        .unwrap();
+
+        let compressed = dir.join("compressed");
+        compress(&Compress {
+            compressor: compressor.clone(),
+            input: Some(input),
+            output: Some(compressed.clone()),
+            chunk_kib: 16,
+        })
+        .unwrap();
+        inspect(&Inspect {
+            compressor: compressor.clone(),
+            input: compressed.clone(),
+        })
+        .unwrap();
+        let decompressed = dir.join("decompressed");
+        decompress(&Decompress {
+            compressor,
+            input: Some(compressed.clone()),
+            output: Some(decompressed.clone()),
+        })
+        .unwrap();
+
+        let recovered = std::fs::read(&decompressed).unwrap();
+        let compressed_len = std::fs::metadata(&compressed).unwrap().len();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(recovered, text);
+        assert!(compressed_len < text.len() as u64);
+    }
+}
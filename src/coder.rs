@@ -1,131 +1,319 @@
-use bytes::{Buf, BufMut};
+use bytes::{Buf, BufMut, BytesMut};
 use serde::{Deserialize, Serialize};
 
-use crate::{factor::FactorType, scratch::Scratch, Error};
+use crate::{factor::FactorType, frame, scratch::Scratch, ChecksumPart, Error};
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
-pub struct ZstdCompressor {
-    level: i32,
+mod codec;
+mod cost;
+
+pub use codec::{
+    BitPackCompressor, Codec, Compressor, Lz4Compressor, RawCompressor, VbyteCompressor,
+    VbyteZstdCompressor, ZstdCompressor,
+};
+pub(crate) use cost::CostModel;
+
+/// Compresses the literal, offset and length streams produced by factorizing a document.
+/// Each stream can use a different [`Codec`].
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct Coder {
+    literals: Codec,
+    offsets: Codec,
+    lens: Codec,
+    checksums: bool,
 }
 
-impl ZstdCompressor {
-    pub(crate) fn new(level: i32) -> Self {
-        Self { level }
+impl Coder {
+    /// Compress all streams with zstd at `lvl`
+    #[must_use]
+    pub fn zstd(lvl: i32) -> Coder {
+        Coder::new(Codec::zstd(lvl))
     }
-}
 
-impl ZstdCompressor {
-    pub fn compress(&self, output: &mut [u8], input: &[u8]) -> Result<usize, Error> {
-        let num_compressed_bytes = if input.is_empty() {
-            0
-        } else {
-            zstd::bulk::compress_to_buffer(input, output, self.level)
-                .map_err(|e| Error::EncodingError { source: e })?
-        };
-        Ok(num_compressed_bytes)
+    /// Compress all streams with `codec`
+    #[must_use]
+    pub fn new(codec: Codec) -> Coder {
+        Coder::per_stream(codec, codec, codec)
     }
 
-    pub fn decompress(&self, input: &[u8], output: &mut [u8]) -> Result<usize, Error> {
-        if input.has_remaining() {
-            let num_decompressed_bytes = zstd::bulk::decompress_to_buffer(input, output)
-                .map_err(|e| Error::DecodingError { source: e })?;
-            return Ok(num_decompressed_bytes);
+    /// Compress each stream with its own codec
+    #[must_use]
+    pub fn per_stream(literals: Codec, offsets: Codec, lens: Codec) -> Coder {
+        Coder {
+            literals,
+            offsets,
+            lens,
+            checksums: false,
         }
-        Ok(0)
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
-pub struct Coder {
-    compressor: ZstdCompressor,
+impl Coder {
+    /// Use `codec` for the literal stream
+    #[must_use]
+    pub fn literals(mut self, codec: Codec) -> Coder {
+        self.literals = codec;
+        self
+    }
+
+    /// Use `codec` for the offset stream
+    #[must_use]
+    pub fn offsets(mut self, codec: Codec) -> Coder {
+        self.offsets = codec;
+        self
+    }
+
+    /// Use `codec` for the length stream
+    #[must_use]
+    pub fn lens(mut self, codec: Codec) -> Coder {
+        self.lens = codec;
+        self
+    }
+
+    /// Store a checksum of each compressed stream and verify it before decompressing
+    #[must_use]
+    pub fn checksums(mut self, enabled: bool) -> Coder {
+        self.checksums = enabled;
+        self
+    }
+
+    pub(crate) fn has_checksums(&self) -> bool {
+        self.checksums
+    }
 }
 
-impl Coder {
-    pub fn zstd(lvl: i32) -> Coder {
-        Coder {
-            compressor: ZstdCompressor::new(lvl),
-        }
+/// The streams a document is split into during encoding
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Stream {
+    /// bytes of all literal factors
+    Literals,
+    /// dictionary offsets of all copy factors
+    Offsets,
+    /// lengths of all factors
+    Lens,
+}
+
+/// Number of bytes each stream occupies in an encoded document
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct StreamSizes {
+    /// bytes of the header storing the stream sizes and checksums
+    pub header: usize,
+    /// bytes of the compressed literal stream
+    pub literals: usize,
+    /// bytes of the compressed offset stream
+    pub offsets: usize,
+    /// bytes of the compressed length stream
+    pub lens: usize,
+}
+
+impl StreamSizes {
+    /// Total number of bytes of the encoded document
+    #[must_use]
+    pub fn total(&self) -> usize {
+        self.header + self.literals + self.offsets + self.lens
+    }
+
+    /// The stream which occupies the most bytes
+    #[must_use]
+    pub fn dominant(&self) -> Stream {
+        [
+            (self.literals, Stream::Literals),
+            (self.offsets, Stream::Offsets),
+            (self.lens, Stream::Lens),
+        ]
+        .into_iter()
+        .max_by_key(|(bytes, _)| *bytes)
+        .map_or(Stream::Literals, |(_, stream)| stream)
     }
 }
 
 impl Default for Coder {
     fn default() -> Coder {
-        Coder {
-            compressor: ZstdCompressor::new(6),
-        }
+        Coder::new(Codec::default())
     }
 }
 
+/// Bytes of the literal, offset and length stream checksums
+const STREAM_CHECKSUM_BYTES: usize = 3 * 4;
+
+/// Bytes of the values of the literal and length streams
+const VALUE_BYTES: usize = std::mem::size_of::<u32>();
+
+fn compress_stream(
+    codec: Codec,
+    input: &[u8],
+    value_bytes: usize,
+    output: &mut BytesMut,
+) -> Result<usize, Error> {
+    if input.is_empty() {
+        return Ok(0);
+    }
+    codec.compress_values(input, value_bytes, output)
+}
+
+fn decompress_stream(
+    codec: Codec,
+    input: &[u8],
+    value_bytes: usize,
+    output: &mut BytesMut,
+) -> Result<usize, Error> {
+    output.clear();
+    if input.is_empty() {
+        return Ok(0);
+    }
+    codec.decompress_values(input, value_bytes, output)
+}
+
 impl Coder {
+    /// Compress the streams in `scratch` whose offsets are `offset_width` bytes wide
     #[tracing::instrument(skip_all)]
+    #[allow(clippy::cast_possible_truncation)]
     pub(crate) fn encode(
         &self,
         mut output: impl BufMut,
         scratch: &mut Scratch,
+        offset_width: usize,
     ) -> Result<usize, Error> {
-        // (1) ensure we have enough space
-        let max_expected = scratch.literals.len() + scratch.offsets.len() + scratch.lens.len();
-        scratch.reserve_encoded(max_expected);
-
-        // (2) encode everything
-        let mut written_bytes = self
-            .compressor
-            .compress(&mut scratch.encoded, &scratch.literals)?;
-        let literal_bytes = written_bytes;
-        let offset_bytes = self
-            .compressor
-            .compress(&mut scratch.encoded[written_bytes..], &scratch.offsets)?;
-        written_bytes += offset_bytes;
-        written_bytes += self
-            .compressor
-            .compress(&mut scratch.encoded[written_bytes..], &scratch.lens)?;
-
-        let mut encode_bytes = written_bytes;
+        // (1) encode everything
+        scratch.encoded.clear();
+        let literal_bytes = compress_stream(
+            self.literals,
+            &scratch.literals,
+            VALUE_BYTES,
+            &mut scratch.encoded,
+        )?;
+        let offset_bytes = compress_stream(
+            self.offsets,
+            &scratch.offsets,
+            offset_width,
+            &mut scratch.encoded,
+        )?;
+        compress_stream(self.lens, &scratch.lens, VALUE_BYTES, &mut scratch.encoded)?;
+
+        // (2) write the header and the compressed streams
+        tracing::debug!(
+            literal_bytes,
+            offset_bytes,
+            len_bytes = scratch.encoded.len() - literal_bytes - offset_bytes,
+            "compressed factor streams"
+        );
+        let mut encode_bytes = scratch.encoded.len();
         encode_bytes += crate::vbyte::encode(&mut output, literal_bytes as u32);
         encode_bytes += crate::vbyte::encode(&mut output, offset_bytes as u32);
+        if self.checksums {
+            let (literals, remainder) = scratch.encoded.split_at(literal_bytes);
+            let (offsets, lens) = remainder.split_at(offset_bytes);
+            for stream in [literals, offsets, lens] {
+                output.put_u32_le(frame::checksum(stream));
+            }
+            encode_bytes += STREAM_CHECKSUM_BYTES;
+        }
 
-        output.put_slice(&scratch.encoded[..written_bytes]);
+        output.put_slice(&scratch.encoded);
 
         Ok(encode_bytes)
     }
 
-    #[tracing::instrument(skip_all)]
-    pub(crate) fn decode(&self, mut input: &[u8], scratch: &mut Scratch) -> Result<(), Error> {
-        let num_literal_bytes = crate::vbyte::decode(&mut input) as usize;
-        let num_offset_bytes = crate::vbyte::decode(&mut input) as usize;
-
-        // (1) ensure we have enough space
-        scratch.reserve_output(input.remaining());
+    /// Sizes of the streams of an encoded document without decompressing them
+    pub(crate) fn stream_sizes(&self, mut input: &[u8]) -> Result<StreamSizes, Error> {
+        let encoded_len = input.len();
+        let literals = crate::vbyte::decode(&mut input)? as usize;
+        let offsets = crate::vbyte::decode(&mut input)? as usize;
+        if self.checksums {
+            input = input.get(STREAM_CHECKSUM_BYTES..).unwrap_or_default();
+        }
+        let header = encoded_len - input.len();
+        let lens = literals
+            .checked_add(offsets)
+            .and_then(|streams| input.len().checked_sub(streams))
+            .ok_or(Error::TruncatedInput)?;
+        Ok(StreamSizes {
+            header,
+            literals,
+            offsets,
+            lens,
+        })
+    }
 
-        let (literal_bytes, remainder) = input.split_at(num_literal_bytes as usize);
-        let (offset_bytes, len_bytes) = remainder.split_at(num_offset_bytes as usize);
+    /// Decompress the streams of `input` whose offsets are `offset_width` bytes wide
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn decode(
+        &self,
+        input: &[u8],
+        scratch: &mut Scratch,
+        offset_width: usize,
+    ) -> Result<(), Error> {
+        let [literal_bytes, offset_bytes, len_bytes] = self.streams(input)?;
+        decompress_stream(
+            self.literals,
+            literal_bytes,
+            VALUE_BYTES,
+            &mut scratch.literals,
+        )?;
+        decompress_stream(
+            self.offsets,
+            offset_bytes,
+            offset_width,
+            &mut scratch.offsets,
+        )?;
+        decompress_stream(self.lens, len_bytes, VALUE_BYTES, &mut scratch.lens)?;
 
-        // (2) perform the decoding
-        let decoded = self
-            .compressor
-            .decompress(literal_bytes, &mut scratch.literals)?;
-        scratch.literals.truncate(decoded);
+        Ok(())
+    }
 
-        let decoded = self
-            .compressor
-            .decompress(offset_bytes, &mut scratch.offsets)?;
-        scratch.offsets.truncate(decoded);
+    /// The compressed literal, offset and length streams of `input` after verifying their
+    /// checksums
+    fn streams<'a>(&self, mut input: &'a [u8]) -> Result<[&'a [u8]; 3], Error> {
+        let num_literal_bytes = crate::vbyte::decode(&mut input)? as usize;
+        let num_offset_bytes = crate::vbyte::decode(&mut input)? as usize;
+        let mut checksums = [0; 3];
+        if self.checksums {
+            if input.len() < STREAM_CHECKSUM_BYTES {
+                return Err(Error::TruncatedInput);
+            }
+            for checksum in &mut checksums {
+                *checksum = input.get_u32_le();
+            }
+        }
 
-        let decoded = self.compressor.decompress(len_bytes, &mut scratch.lens)?;
-        scratch.lens.truncate(decoded);
+        let (literal_bytes, remainder) = input
+            .split_at_checked(num_literal_bytes)
+            .ok_or(Error::TruncatedInput)?;
+        let (offset_bytes, len_bytes) = remainder
+            .split_at_checked(num_offset_bytes)
+            .ok_or(Error::TruncatedInput)?;
 
-        Ok(())
+        if self.checksums {
+            let streams = [
+                (literal_bytes, Stream::Literals),
+                (offset_bytes, Stream::Offsets),
+                (len_bytes, Stream::Lens),
+            ];
+            for ((bytes, stream), expected) in streams.into_iter().zip(checksums) {
+                if frame::checksum(bytes) != expected {
+                    return Err(Error::ChecksumMismatch {
+                        part: ChecksumPart::Stream(stream),
+                    });
+                }
+            }
+        }
+        Ok([literal_bytes, offset_bytes, len_bytes])
     }
 
     #[tracing::instrument(skip_all)]
-    pub(crate) fn store_factor(&self, scratch: &mut Scratch, factor: FactorType) {
+    #[allow(clippy::unused_self, clippy::cast_possible_truncation)]
+    pub(crate) fn store_factor(
+        &self,
+        scratch: &mut Scratch,
+        factor: FactorType,
+        offset_bytes: usize,
+    ) {
         match factor {
             FactorType::Literal(literal) => {
                 scratch.lens.put_u32(literal.len() as u32);
                 scratch.literals.put_slice(&literal);
             }
             FactorType::Copy { offset, len } => {
-                scratch.offsets.put_u32(offset);
+                scratch.offsets.put_uint(offset as u64, offset_bytes);
                 scratch.lens.put_u32(len);
             }
         }
@@ -149,7 +337,7 @@ mod tests {
             };
             let mut output = Vec::new();
             let coder = Coder::default();
-            let encoded_len = coder.encode(&mut output, &mut scratch)?;
+            let encoded_len = coder.encode(&mut output, &mut scratch, VALUE_BYTES)?;
             assert_eq!(encoded_len,output.len());
         }
     }
@@ -165,7 +353,7 @@ mod tests {
             };
             let mut output = Vec::new();
             let coder = Coder::zstd(3);
-            let encoded_len = coder.encode(&mut output, &mut scratch)?;
+            let encoded_len = coder.encode(&mut output, &mut scratch, VALUE_BYTES)?;
             assert_eq!(encoded_len,output.len());
             dbg!(encoded_len);
 
@@ -176,11 +364,150 @@ mod tests {
                 lens: BytesMut::with_capacity(1024 * 1024),
             };
 
-            coder.decode(&output,&mut scratch2)?;
+            coder.decode(&output,&mut scratch2, VALUE_BYTES)?;
 
             assert_eq!(scratch.literals,scratch2.literals);
             assert_eq!(scratch.offsets,scratch2.offsets);
             assert_eq!(scratch.lens,scratch2.lens);
         }
     }
+
+    #[test]
+    fn dominant_stream() {
+        let sizes = StreamSizes {
+            header: 2,
+            literals: 10,
+            offsets: 30,
+            lens: 20,
+        };
+        assert_eq!(sizes.dominant(), Stream::Offsets);
+        assert_eq!(sizes.total(), 62);
+    }
+
+    fn codec() -> impl Strategy<Value = Codec> {
+        prop_oneof![
+            (-5..20i32).prop_map(Codec::zstd),
+            Just(Codec::lz4()),
+            Just(Codec::vbyte()),
+            (-5..20i32).prop_map(Codec::vbyte_zstd),
+            Just(Codec::bitpack()),
+            Just(Codec::raw()),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn codec_recover(codec in codec(), input: Vec<u8>) {
+            prop_assume!(!input.is_empty());
+            let mut compressed = BytesMut::new();
+            let compressed_len = codec.compress(&input, &mut compressed)?;
+            assert_eq!(compressed_len, compressed.len());
+
+            let mut decompressed = BytesMut::new();
+            let decompressed_len = codec.decompress(&compressed, &mut decompressed)?;
+            assert_eq!(decompressed_len, input.len());
+            assert_eq!(&decompressed[..], &input[..]);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn codec_recover_values(codec in codec(), value_bytes in 1..=8usize, input: Vec<u8>) {
+            prop_assume!(!input.is_empty());
+            let mut compressed = BytesMut::new();
+            let compressed_len = codec.compress_values(&input, value_bytes, &mut compressed)?;
+            assert_eq!(compressed_len, compressed.len());
+
+            let mut decompressed = BytesMut::new();
+            let decompressed_len = codec.decompress_values(&compressed, value_bytes, &mut decompressed)?;
+            assert_eq!(decompressed_len, input.len());
+            assert_eq!(&decompressed[..], &input[..]);
+        }
+    }
+
+    #[test]
+    fn bitpack_wide_values() {
+        // 5 byte offsets below 2^33 do not straddle words and pack into 33 bits each
+        let mut input = BytesMut::new();
+        for offset in [0u64, 1 << 32, (1 << 33) - 1] {
+            input.put_uint(offset, 5);
+        }
+        let mut compressed = BytesMut::new();
+        Codec::bitpack()
+            .compress_values(&input, 5, &mut compressed)
+            .unwrap();
+        assert_eq!(compressed[1], 33);
+        assert_eq!(compressed.len(), 2 + (3 * 33usize).div_ceil(8));
+    }
+
+    #[test]
+    fn reject_oversized_lz4_block() {
+        let mut input = BytesMut::new();
+        input.put_u32_le(u32::MAX);
+        input.put_u8(0);
+        let result = Codec::lz4().decompress(&input, &mut BytesMut::new());
+        assert!(matches!(result, Err(Error::DecodingError { .. })));
+    }
+
+    #[test]
+    fn reject_bitpack_size_beyond_input() {
+        let mut input = BytesMut::new();
+        crate::vbyte::encode(&mut input, u32::MAX);
+        input.put_u8(0);
+        let result = Codec::bitpack().decompress(&input, &mut BytesMut::new());
+        assert!(matches!(result, Err(Error::DecodingError { .. })));
+
+        let mut input = BytesMut::new();
+        crate::vbyte::encode(&mut input, 1024);
+        input.put_slice(&[8, 0, 0]);
+        let result = Codec::bitpack().decompress(&input, &mut BytesMut::new());
+        assert!(matches!(result, Err(Error::DecodingError { .. })));
+    }
+
+    proptest! {
+        #[test]
+        fn recover_per_stream(literal_codec in codec(), offset_codec in codec(), len_codec in codec(), literals: Vec<u8>,offsets: Vec<u8>,lens: Vec<u8>) {
+            let mut scratch =  Scratch {
+                encoded: BytesMut::new(),
+                literals: BytesMut::from(&literals[..]),
+                offsets: BytesMut::from(&offsets[..]),
+                lens: BytesMut::from(&lens[..]),
+            };
+            let mut output = Vec::new();
+            let coder = Coder::per_stream(literal_codec, offset_codec, len_codec);
+            let encoded_len = coder.encode(&mut output, &mut scratch, VALUE_BYTES)?;
+            assert_eq!(encoded_len,output.len());
+            assert_eq!(coder.stream_sizes(&output)?.total(),output.len());
+
+            let mut scratch2 = Scratch::default();
+            coder.decode(&output,&mut scratch2, VALUE_BYTES)?;
+
+            assert_eq!(scratch.literals,scratch2.literals);
+            assert_eq!(scratch.offsets,scratch2.offsets);
+            assert_eq!(scratch.lens,scratch2.lens);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn detect_corrupt_stream(literals: Vec<u8>, offsets: Vec<u8>, lens: Vec<u8>, flip: usize) {
+            let mut scratch =  Scratch {
+                encoded: BytesMut::new(),
+                literals: BytesMut::from(&literals[..]),
+                offsets: BytesMut::from(&offsets[..]),
+                lens: BytesMut::from(&lens[..]),
+            };
+            let mut output = Vec::new();
+            let coder = Coder::zstd(3).checksums(true);
+            coder.encode(&mut output, &mut scratch, VALUE_BYTES)?;
+            assert_eq!(coder.stream_sizes(&output)?.total(),output.len());
+
+            let header = coder.stream_sizes(&output)?.header;
+            prop_assume!(output.len() > header);
+            let flip = header + flip % (output.len() - header);
+            output[flip] ^= 1;
+            let result = coder.decode(&output, &mut Scratch::default(), VALUE_BYTES);
+            assert!(matches!(result, Err(Error::ChecksumMismatch { part: ChecksumPart::Stream(_) })));
+        }
+    }
 }
@@ -0,0 +1,466 @@
+use bytes::{Buf, BufMut, BytesMut};
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Compression codec applied to one of the literal, offset or length streams
+pub trait Compressor {
+    /// Compress the non-empty `input` and append it to `output`. Returns the number of bytes appended.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the codec can not compress `input`
+    fn compress(&self, input: &[u8], output: &mut BytesMut) -> Result<usize, Error>;
+
+    /// Decompress the non-empty `input` and append it to `output`. Returns the number of bytes appended.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `input` was not produced by [`Compressor::compress`] of the same codec
+    fn decompress(&self, input: &[u8], output: &mut BytesMut) -> Result<usize, Error>;
+}
+
+/// Grow `output` by `max_bytes`, let `f` write into the new space and shrink `output`
+/// to the number of bytes `f` actually wrote.
+fn write_into_spare(
+    output: &mut BytesMut,
+    max_bytes: usize,
+    f: impl FnOnce(&mut [u8]) -> Result<usize, Error>,
+) -> Result<usize, Error> {
+    let start = output.len();
+    output.resize(start + max_bytes, 0);
+    let written = f(&mut output[start..]);
+    output.truncate(start + *written.as_ref().unwrap_or(&0));
+    written
+}
+
+fn invalid_data(error: impl std::error::Error + Send + Sync + 'static) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+}
+
+/// zstd compression at a given level
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct ZstdCompressor {
+    level: i32,
+}
+
+impl ZstdCompressor {
+    /// zstd compressor with compression `level`
+    #[must_use]
+    pub fn new(level: i32) -> Self {
+        Self { level }
+    }
+}
+
+impl Compressor for ZstdCompressor {
+    fn compress(&self, input: &[u8], output: &mut BytesMut) -> Result<usize, Error> {
+        let max_bytes = zstd::zstd_safe::compress_bound(input.len());
+        write_into_spare(output, max_bytes, |buf| {
+            zstd::bulk::compress_to_buffer(input, buf, self.level)
+                .map_err(|e| Error::EncodingError { source: e })
+        })
+    }
+
+    fn decompress(&self, input: &[u8], output: &mut BytesMut) -> Result<usize, Error> {
+        let content_size = zstd::zstd_safe::get_frame_content_size(input);
+        let content_size = match content_size {
+            zstd::zstd_safe::CONTENTSIZE_ERROR | zstd::zstd_safe::CONTENTSIZE_UNKNOWN => None,
+            content_size => usize::try_from(content_size).ok(),
+        }
+        .filter(|content_size| *content_size <= max_zstd_content_size(input.len()))
+        .ok_or_else(|| Error::DecodingError {
+            source: std::io::ErrorKind::InvalidData.into(),
+        })?;
+        write_into_spare(output, content_size, |buf| {
+            zstd::bulk::decompress_to_buffer(input, buf)
+                .map_err(|e| Error::DecodingError { source: e })
+        })
+    }
+}
+
+/// Upper bound of the content size of a zstd frame of `compressed_len` bytes: a block
+/// decompresses to at most 128 KiB and occupies at least 4 bytes. Guards against
+/// allocating the content size of corrupt frame headers.
+fn max_zstd_content_size(compressed_len: usize) -> usize {
+    compressed_len.saturating_mul(128 * 1024 / 4)
+}
+
+/// LZ4 block compression. Fast, but compresses worse than zstd.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Deserialize, Serialize)]
+pub struct Lz4Compressor;
+
+impl Compressor for Lz4Compressor {
+    #[allow(clippy::cast_possible_truncation)]
+    fn compress(&self, input: &[u8], output: &mut BytesMut) -> Result<usize, Error> {
+        output.put_u32_le(input.len() as u32);
+        let max_bytes = lz4_flex::block::get_maximum_output_size(input.len());
+        let written = write_into_spare(output, max_bytes, |buf| {
+            lz4_flex::block::compress_into(input, buf).map_err(|e| Error::EncodingError {
+                source: invalid_data(e),
+            })
+        })?;
+        Ok(std::mem::size_of::<u32>() + written)
+    }
+
+    fn decompress(&self, mut input: &[u8], output: &mut BytesMut) -> Result<usize, Error> {
+        if input.len() < std::mem::size_of::<u32>() {
+            return Err(Error::TruncatedInput);
+        }
+        let decompressed_size = input.get_u32_le() as usize;
+        if decompressed_size > max_lz4_content_size(input.len()) {
+            return Err(Error::DecodingError {
+                source: std::io::ErrorKind::InvalidData.into(),
+            });
+        }
+        write_into_spare(output, decompressed_size, |buf| {
+            lz4_flex::block::decompress_into(input, buf).map_err(|e| Error::DecodingError {
+                source: invalid_data(e),
+            })
+        })
+    }
+}
+
+/// Upper bound of the decompressed size of a LZ4 block of `compressed_len` bytes: a
+/// sequence expands to at most 255 bytes per byte of its length. Guards against allocating
+/// the size prefix of corrupt blocks.
+fn max_lz4_content_size(compressed_len: usize) -> usize {
+    compressed_len.saturating_mul(255)
+}
+
+/// Bytes of the big endian values the integer codecs split a stream into. Offsets of a
+/// [`crate::IndexWidth::Wide`] index can be wider, see [`Codec::compress_values`].
+const VALUE_BYTES: usize = std::mem::size_of::<u32>();
+
+/// Interprets the stream as big endian `u32` values and stores each as a vbyte.
+/// Cheap and effective for the length stream which is dominated by small values.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Deserialize, Serialize)]
+pub struct VbyteCompressor;
+
+impl VbyteCompressor {
+    #[allow(clippy::cast_possible_truncation)]
+    fn compress_values(input: &[u8], value_bytes: usize, output: &mut BytesMut) -> usize {
+        let mut written = crate::vbyte::encode(&mut *output, input.len() as u32);
+        for value in be_values(input, value_bytes) {
+            written += crate::vbyte::encode_u64(&mut *output, value);
+        }
+        written
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn decompress_values(
+        mut input: &[u8],
+        value_bytes: usize,
+        output: &mut BytesMut,
+    ) -> Result<usize, Error> {
+        let decompressed_size = crate::vbyte::decode(&mut input)? as usize;
+        let start = output.len();
+        while output.len() - start < decompressed_size {
+            let value = crate::vbyte::decode_u64(&mut input)?;
+            if value_bits(value) > value_bytes as u32 * u8::BITS {
+                return Err(Error::DecodingError {
+                    source: std::io::ErrorKind::InvalidData.into(),
+                });
+            }
+            output.put_uint(value, value_bytes);
+        }
+        output.truncate(start + decompressed_size);
+        Ok(decompressed_size)
+    }
+}
+
+impl Compressor for VbyteCompressor {
+    fn compress(&self, input: &[u8], output: &mut BytesMut) -> Result<usize, Error> {
+        Ok(Self::compress_values(input, VALUE_BYTES, output))
+    }
+
+    fn decompress(&self, input: &[u8], output: &mut BytesMut) -> Result<usize, Error> {
+        Self::decompress_values(input, VALUE_BYTES, output)
+    }
+}
+
+/// Interprets the stream as big endian `u32` values and packs each into the minimum number
+/// of bits required to represent the largest value. Suited for the near-uniform offset stream.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Deserialize, Serialize)]
+pub struct BitPackCompressor;
+
+impl BitPackCompressor {
+    #[allow(clippy::cast_possible_truncation)]
+    fn compress_values(input: &[u8], value_bytes: usize, output: &mut BytesMut) -> usize {
+        let start = output.len();
+        crate::vbyte::encode(&mut *output, input.len() as u32);
+        let max_value = be_values(input, value_bytes).max().unwrap_or(0);
+        // at least one bit per value so the packed size bounds the decompressed size
+        let width = value_bits(max_value).max(1);
+        output.put_u8(width as u8);
+
+        let mut buffer = 0u128;
+        let mut buffered_bits = 0;
+        for value in be_values(input, value_bytes) {
+            buffer |= u128::from(value) << buffered_bits;
+            buffered_bits += width;
+            while buffered_bits >= u8::BITS {
+                output.put_u8(buffer as u8);
+                buffer >>= u8::BITS;
+                buffered_bits -= u8::BITS;
+            }
+        }
+        if buffered_bits > 0 {
+            output.put_u8(buffer as u8);
+        }
+        output.len() - start
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn decompress_values(
+        mut input: &[u8],
+        value_bytes: usize,
+        output: &mut BytesMut,
+    ) -> Result<usize, Error> {
+        let decompressed_size = crate::vbyte::decode(&mut input)? as usize;
+        if !input.has_remaining() {
+            return Err(Error::TruncatedInput);
+        }
+        let width = u32::from(input.get_u8());
+        let num_values = decompressed_size.div_ceil(value_bytes) as u64;
+        let packed_bits = (input.len() as u64).saturating_mul(u64::from(u8::BITS));
+        if width > value_bytes as u32 * u8::BITS
+            || (width == 0 && decompressed_size != 0)
+            || num_values.saturating_mul(u64::from(width)) > packed_bits
+        {
+            return Err(Error::DecodingError {
+                source: std::io::ErrorKind::InvalidData.into(),
+            });
+        }
+        let mask = (1u128 << width) - 1;
+        let start = output.len();
+
+        let mut buffer = 0u128;
+        let mut buffered_bits = 0;
+        while output.len() - start < decompressed_size {
+            while buffered_bits < width {
+                if !input.has_remaining() {
+                    return Err(Error::TruncatedInput);
+                }
+                buffer |= u128::from(input.get_u8()) << buffered_bits;
+                buffered_bits += u8::BITS;
+            }
+            output.put_uint((buffer & mask) as u64, value_bytes);
+            buffer >>= width;
+            buffered_bits -= width;
+        }
+        output.truncate(start + decompressed_size);
+        Ok(decompressed_size)
+    }
+}
+
+impl Compressor for BitPackCompressor {
+    fn compress(&self, input: &[u8], output: &mut BytesMut) -> Result<usize, Error> {
+        Ok(Self::compress_values(input, VALUE_BYTES, output))
+    }
+
+    fn decompress(&self, input: &[u8], output: &mut BytesMut) -> Result<usize, Error> {
+        Self::decompress_values(input, VALUE_BYTES, output)
+    }
+}
+
+/// [`VbyteCompressor`] followed by zstd at a given level. The zstd entropy coder
+/// removes most of the remaining redundancy of skewed small values such as the length stream.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct VbyteZstdCompressor {
+    level: i32,
+}
+
+impl VbyteZstdCompressor {
+    /// vbyte followed by zstd compression at `level`
+    #[must_use]
+    pub fn new(level: i32) -> Self {
+        Self { level }
+    }
+}
+
+impl VbyteZstdCompressor {
+    fn compress_values(
+        self,
+        input: &[u8],
+        value_bytes: usize,
+        output: &mut BytesMut,
+    ) -> Result<usize, Error> {
+        let mut vbytes = BytesMut::new();
+        VbyteCompressor::compress_values(input, value_bytes, &mut vbytes);
+        ZstdCompressor::new(self.level).compress(&vbytes, output)
+    }
+
+    fn decompress_values(
+        self,
+        input: &[u8],
+        value_bytes: usize,
+        output: &mut BytesMut,
+    ) -> Result<usize, Error> {
+        let mut vbytes = BytesMut::new();
+        ZstdCompressor::new(self.level).decompress(input, &mut vbytes)?;
+        VbyteCompressor::decompress_values(&vbytes, value_bytes, output)
+    }
+}
+
+impl Compressor for VbyteZstdCompressor {
+    fn compress(&self, input: &[u8], output: &mut BytesMut) -> Result<usize, Error> {
+        self.compress_values(input, VALUE_BYTES, output)
+    }
+
+    fn decompress(&self, input: &[u8], output: &mut BytesMut) -> Result<usize, Error> {
+        self.decompress_values(input, VALUE_BYTES, output)
+    }
+}
+
+/// The stream split into big endian values of `value_bytes` bytes, the last one padded
+/// with zeros
+fn be_values(input: &[u8], value_bytes: usize) -> impl Iterator<Item = u64> + '_ {
+    input.chunks(value_bytes).map(move |chunk| {
+        let mut word = [0; std::mem::size_of::<u64>()];
+        word[..chunk.len()].copy_from_slice(chunk);
+        u64::from_be_bytes(word) >> (u8::BITS as usize * (word.len() - value_bytes))
+    })
+}
+
+/// Number of bits required to represent `value`
+fn value_bits(value: u64) -> u32 {
+    u64::BITS - value.leading_zeros()
+}
+
+/// Stores the stream uncompressed
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Deserialize, Serialize)]
+pub struct RawCompressor;
+
+impl Compressor for RawCompressor {
+    fn compress(&self, input: &[u8], output: &mut BytesMut) -> Result<usize, Error> {
+        output.put_slice(input);
+        Ok(input.len())
+    }
+
+    fn decompress(&self, input: &[u8], output: &mut BytesMut) -> Result<usize, Error> {
+        output.put_slice(input);
+        Ok(input.len())
+    }
+}
+
+/// The codecs available for compressing the factor streams
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub enum Codec {
+    /// see [`ZstdCompressor`]
+    Zstd(ZstdCompressor),
+    /// see [`Lz4Compressor`]
+    Lz4(Lz4Compressor),
+    /// see [`VbyteCompressor`]
+    Vbyte(VbyteCompressor),
+    /// see [`VbyteZstdCompressor`]
+    VbyteZstd(VbyteZstdCompressor),
+    /// see [`BitPackCompressor`]
+    BitPack(BitPackCompressor),
+    /// see [`RawCompressor`]
+    Raw(RawCompressor),
+}
+
+impl Codec {
+    /// zstd compression at `level`
+    #[must_use]
+    pub fn zstd(level: i32) -> Codec {
+        Codec::Zstd(ZstdCompressor::new(level))
+    }
+
+    /// LZ4 block compression
+    #[must_use]
+    pub fn lz4() -> Codec {
+        Codec::Lz4(Lz4Compressor)
+    }
+
+    /// vbyte coding of `u32` values
+    #[must_use]
+    pub fn vbyte() -> Codec {
+        Codec::Vbyte(VbyteCompressor)
+    }
+
+    /// vbyte coding of `u32` values followed by zstd compression at `level`
+    #[must_use]
+    pub fn vbyte_zstd(level: i32) -> Codec {
+        Codec::VbyteZstd(VbyteZstdCompressor::new(level))
+    }
+
+    /// bit-packing of `u32` values
+    #[must_use]
+    pub fn bitpack() -> Codec {
+        Codec::BitPack(BitPackCompressor)
+    }
+
+    /// no compression
+    #[must_use]
+    pub fn raw() -> Codec {
+        Codec::Raw(RawCompressor)
+    }
+
+    /// Compress `input` made up of big endian values of `value_bytes` bytes, between 1 and 8.
+    /// The integer codecs code each value, the others ignore the value width.
+    pub(crate) fn compress_values(
+        self,
+        input: &[u8],
+        value_bytes: usize,
+        output: &mut BytesMut,
+    ) -> Result<usize, Error> {
+        match self {
+            Codec::Vbyte(_) => Ok(VbyteCompressor::compress_values(input, value_bytes, output)),
+            Codec::VbyteZstd(compressor) => compressor.compress_values(input, value_bytes, output),
+            Codec::BitPack(_) => Ok(BitPackCompressor::compress_values(
+                input,
+                value_bytes,
+                output,
+            )),
+            Codec::Zstd(_) | Codec::Lz4(_) | Codec::Raw(_) => self.compress(input, output),
+        }
+    }
+
+    /// Decompress `input` produced by [`Codec::compress_values`] with the same `value_bytes`
+    pub(crate) fn decompress_values(
+        self,
+        input: &[u8],
+        value_bytes: usize,
+        output: &mut BytesMut,
+    ) -> Result<usize, Error> {
+        match self {
+            Codec::Vbyte(_) => VbyteCompressor::decompress_values(input, value_bytes, output),
+            Codec::VbyteZstd(compressor) => {
+                compressor.decompress_values(input, value_bytes, output)
+            }
+            Codec::BitPack(_) => BitPackCompressor::decompress_values(input, value_bytes, output),
+            Codec::Zstd(_) | Codec::Lz4(_) | Codec::Raw(_) => self.decompress(input, output),
+        }
+    }
+}
+
+impl Default for Codec {
+    fn default() -> Codec {
+        Codec::zstd(6)
+    }
+}
+
+impl Compressor for Codec {
+    fn compress(&self, input: &[u8], output: &mut BytesMut) -> Result<usize, Error> {
+        match self {
+            Codec::Zstd(compressor) => compressor.compress(input, output),
+            Codec::Lz4(compressor) => compressor.compress(input, output),
+            Codec::Vbyte(compressor) => compressor.compress(input, output),
+            Codec::VbyteZstd(compressor) => compressor.compress(input, output),
+            Codec::BitPack(compressor) => compressor.compress(input, output),
+            Codec::Raw(compressor) => compressor.compress(input, output),
+        }
+    }
+
+    fn decompress(&self, input: &[u8], output: &mut BytesMut) -> Result<usize, Error> {
+        match self {
+            Codec::Zstd(compressor) => compressor.decompress(input, output),
+            Codec::Lz4(compressor) => compressor.decompress(input, output),
+            Codec::Vbyte(compressor) => compressor.decompress(input, output),
+            Codec::VbyteZstd(compressor) => compressor.decompress(input, output),
+            Codec::BitPack(compressor) => compressor.decompress(input, output),
+            Codec::Raw(compressor) => compressor.decompress(input, output),
+        }
+    }
+}
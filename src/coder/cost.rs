@@ -0,0 +1,63 @@
+use super::Codec;
+use crate::config;
+
+/// Number of bits required to represent `value`
+fn bits(value: u64) -> u64 {
+    u64::from(u64::BITS - value.max(1).leading_zeros())
+}
+
+fn vbyte_bits(value: u64) -> u64 {
+    8 * bits(value).div_ceil(7)
+}
+
+/// Estimated number of bits the configured codecs spend on the factors of a document.
+/// Used to weigh literals against copies when searching for the cheapest factorization.
+pub(crate) struct CostModel {
+    literal_byte_bits: u64,
+    offset_bits: u64,
+    lens: Codec,
+}
+
+impl CostModel {
+    pub(crate) fn new(config: &config::Configuration, dict_len: usize) -> CostModel {
+        let coder = &config.factor_compression;
+        let offset_bytes = config.index_width.offset_bytes(dict_len) as u64;
+        let literal_byte_bits = match coder.literals {
+            Codec::Zstd(_) | Codec::VbyteZstd(_) => 5,
+            Codec::Lz4(_) => 6,
+            Codec::Vbyte(_) | Codec::BitPack(_) | Codec::Raw(_) => 8,
+        };
+        let dict_len = dict_len as u64;
+        let offset_bits = match coder.offsets {
+            Codec::Zstd(_) | Codec::VbyteZstd(_) | Codec::BitPack(_) => bits(dict_len),
+            Codec::Vbyte(_) => vbyte_bits(dict_len),
+            Codec::Lz4(_) | Codec::Raw(_) => offset_bytes * u64::from(u8::BITS),
+        };
+        CostModel {
+            literal_byte_bits,
+            offset_bits,
+            lens: coder.lens,
+        }
+    }
+
+    fn len_bits(&self, len: u64) -> u64 {
+        match self.lens {
+            // skewed small values end up close to their Elias-gamma code length
+            Codec::Zstd(_) | Codec::VbyteZstd(_) => 2 * bits(len) - 1,
+            Codec::Vbyte(_) => vbyte_bits(len),
+            Codec::BitPack(_) => bits(len),
+            Codec::Lz4(_) | Codec::Raw(_) => u64::from(u32::BITS),
+        }
+    }
+
+    /// Estimated bits of a literal factor of `len` bytes
+    pub(crate) fn literal(&self, len: usize) -> u64 {
+        let len = len as u64;
+        len * self.literal_byte_bits + self.len_bits(len)
+    }
+
+    /// Estimated bits of a copy factor of `len` bytes
+    pub(crate) fn copy(&self, len: u32) -> u64 {
+        self.offset_bits + self.len_bits(u64::from(len))
+    }
+}
@@ -1,6 +1,51 @@
 use crate::coder;
 use serde::{Deserialize, Serialize};
 
+/// How a document is split into factors
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Deserialize, Serialize)]
+pub enum ParsingStrategy {
+    /// Always take the longest match at the current position
+    #[default]
+    Greedy,
+    /// Before copying the longest match, check whether a match starting one or two bytes
+    /// later reaches further and if so emit the skipped bytes as a literal instead
+    Lazy,
+    /// Pick the factorization with the smallest estimated encoded size. Considers every
+    /// match at every position and is therefore considerably slower than greedy parsing.
+    Optimal,
+}
+
+/// Width of the dictionary index and of the offsets in the encoded format
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Deserialize, Serialize)]
+pub enum IndexWidth {
+    /// 32-bit suffix array and 4 byte offsets, for dictionaries up to 2 GiB
+    #[default]
+    Compact,
+    /// 64-bit suffix array and offsets stored in as few bytes as the dictionary length allows
+    Wide,
+}
+
+impl IndexWidth {
+    /// Bytes of a suffix array entry
+    pub(crate) fn position_bytes(self) -> usize {
+        match self {
+            IndexWidth::Compact => std::mem::size_of::<u32>(),
+            IndexWidth::Wide => std::mem::size_of::<u64>(),
+        }
+    }
+
+    /// Bytes of an offset into a dictionary of `dict_len` bytes in the offset stream
+    pub(crate) fn offset_bytes(self, dict_len: usize) -> usize {
+        match self {
+            IndexWidth::Compact => std::mem::size_of::<u32>(),
+            IndexWidth::Wide => {
+                let bits = usize::BITS - dict_len.leading_zeros();
+                (bits as usize).div_ceil(8).max(1)
+            }
+        }
+    }
+}
+
 /// Compression configuration
 #[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
 pub struct Configuration {
@@ -8,6 +53,10 @@ pub struct Configuration {
     pub literal_threshold: u32,
     /// Compression codec for factors, literals
     pub factor_compression: coder::Coder,
+    /// Strategy used to split documents into factors
+    pub parsing: ParsingStrategy,
+    /// Width of the dictionary index, switched to wide for dictionaries over 2 GiB
+    pub index_width: IndexWidth,
 }
 
 impl Configuration {
@@ -15,6 +64,8 @@ impl Configuration {
         Configuration {
             literal_threshold: 3,
             factor_compression: coder::Coder::default(),
+            parsing: ParsingStrategy::default(),
+            index_width: IndexWidth::default(),
         }
     }
 }
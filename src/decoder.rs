@@ -4,7 +4,7 @@ use crate::{
     coder, config,
     dict::{self},
     factor::FactorType,
-    scratch, Error,
+    frame, scratch, Error,
 };
 use serde::{Deserialize, Serialize};
 
@@ -29,59 +29,443 @@ impl Decoder {
     pub(crate) fn decode(
         &self,
         dict: &dict::Dictionary,
+        fingerprint: frame::Fingerprint,
         input: &[u8],
-        mut output: impl std::io::Write,
+        output: impl std::io::Write,
+    ) -> Result<usize, Error> {
+        let mut scratch = self.scratch.get();
+        let decode_output = self.decode_with(dict, fingerprint, input, output, &mut scratch);
+        self.scratch.release(scratch);
+        decode_output
+    }
+
+    /// Decode `input` using the scratch space of the caller instead of the shared pool
+    pub(crate) fn decode_with(
+        &self,
+        dict: &dict::Dictionary,
+        fingerprint: frame::Fingerprint,
+        mut input: &[u8],
+        output: impl std::io::Write,
+        scratch: &mut scratch::Scratch,
+    ) -> Result<usize, Error> {
+        let header = frame::FrameHeader::read(&mut input)?;
+        header.check(fingerprint)?;
+        if header.checksum.is_none() {
+            let decoded =
+                self.decode_factors_with(dict, input, None, 0..usize::MAX, output, scratch)?;
+            header.verify_len(decoded)?;
+            return Ok(decoded);
+        }
+        let mut output = frame::ChecksumWriter::new(output);
+        let decoded =
+            self.decode_factors_with(dict, input, None, 0..usize::MAX, &mut output, scratch)?;
+        header.verify(output.checksum())?;
+        header.verify_len(decoded)?;
+        Ok(decoded)
+    }
+
+    /// Decode the document in `input` straight into `output`, which must hold at least the
+    /// decoded length stored in the frame
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn decode_into(
+        &self,
+        dict: &dict::Dictionary,
+        fingerprint: frame::Fingerprint,
+        mut input: &[u8],
+        output: &mut [u8],
+    ) -> Result<usize, Error> {
+        let header = frame::FrameHeader::read(&mut input)?;
+        header.check(fingerprint)?;
+        let required = usize::try_from(header.decoded_len).unwrap_or(usize::MAX);
+        if required > output.len() {
+            return Err(Error::OutputTooSmall {
+                required,
+                available: output.len(),
+            });
+        }
+        let output = &mut output[..required];
+
+        let mut scratch = self.scratch.get();
+        scratch.clear();
+
+        let decode_output = self.decompress(dict, input, &mut scratch).and_then(|()| {
+            let offset_bytes = self.config.index_width.offset_bytes(dict.len());
+            let mut factors = EncodedFactorIterator::new(&mut scratch, &self.config, offset_bytes);
+            // with the factor lengths summing to the stored length, which is the length of
+            // `output`, none of the copies below can write past the end of `output`
+            let found = factors.total_len()?;
+            if found != header.decoded_len {
+                return Err(Error::DecodedLengthMismatch {
+                    expected: header.decoded_len,
+                    found,
+                });
+            }
+            let mut decoded = 0;
+            let mut put = |bytes: &[u8]| {
+                output[decoded..decoded + bytes.len()].copy_from_slice(bytes);
+                decoded += bytes.len();
+            };
+            for factor in factors.by_ref() {
+                match factor? {
+                    FactorType::Literal(literal) => put(&literal),
+                    FactorType::Copy { offset, len } => put(&dict[copy_range(dict, offset, len)?]),
+                }
+            }
+            factors.check_exhausted()?;
+            Ok(decoded)
+        });
+
+        self.scratch.release(scratch);
+        let decoded = decode_output?;
+        if header.checksum.is_some() {
+            header.verify(frame::checksum(&output[..decoded]))?;
+        }
+        Ok(decoded)
+    }
+
+    /// Decode `num_factors` factors after skipping the first `skip_factors` factors of `input`,
+    /// returns the number of bytes written. Fails with [`Error::InvalidArchive`] unless the
+    /// length stream of `input` holds exactly `total_factors` factors.
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn decode_factors(
+        &self,
+        dict: &dict::Dictionary,
+        input: &[u8],
+        total_factors: usize,
+        skip_factors: usize,
+        num_factors: usize,
+        output: impl std::io::Write,
     ) -> Result<usize, Error> {
         let mut scratch = self.scratch.get();
+        let range = skip_factors..skip_factors.saturating_add(num_factors);
+        let decode_output = self.decode_factors_with(
+            dict,
+            input,
+            Some(total_factors),
+            range,
+            output,
+            &mut scratch,
+        );
+        self.scratch.release(scratch);
+        decode_output
+    }
+
+    /// Decode the factors `range` of `input`, checking that `input` holds `total_factors`
+    /// factors if given
+    fn decode_factors_with(
+        &self,
+        dict: &dict::Dictionary,
+        input: &[u8],
+        total_factors: Option<usize>,
+        range: std::ops::Range<usize>,
+        mut output: impl std::io::Write,
+        scratch: &mut scratch::Scratch,
+    ) -> Result<usize, Error> {
         scratch.clear();
+        self.decompress(dict, input, scratch).and_then(|()| {
+            let stored_factors = scratch.lens.len() / std::mem::size_of::<u32>();
+            if total_factors.is_some_and(|total| total != stored_factors) {
+                return Err(Error::InvalidArchive);
+            }
+            let offset_bytes = self.config.index_width.offset_bytes(dict.len());
+            let mut factors = EncodedFactorIterator::new(scratch, &self.config, offset_bytes);
+            for _ in 0..range.start {
+                factors.skip_factor()?;
+            }
+            let mut decoded = 0;
+            for factor in factors.by_ref().take(range.len()) {
+                match factor? {
+                    FactorType::Literal(literal) => {
+                        output.write_all(&literal)?;
+                        decoded += literal.len();
+                    }
+                    FactorType::Copy { offset, len } => {
+                        output.write_all(&dict[copy_range(dict, offset, len)?])?;
+                        decoded += len as usize;
+                    }
+                }
+            }
+            factors.check_exhausted()?;
+            Ok(decoded)
+        })
+    }
 
-        self.coder.decode(input, &mut scratch)?;
+    /// Decode bytes `range` of the document in `input`. Factors ending before the range are
+    /// skipped by their length alone and decoding stops at the end of the range.
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn decode_range(
+        &self,
+        dict: &dict::Dictionary,
+        fingerprint: frame::Fingerprint,
+        mut input: &[u8],
+        range: std::ops::Range<usize>,
+        mut output: impl std::io::Write,
+    ) -> Result<usize, Error> {
+        let header = frame::FrameHeader::read(&mut input)?;
+        header.check(fingerprint)?;
+        let decoded_len = usize::try_from(header.decoded_len).unwrap_or(usize::MAX);
+        if range.start > range.end || range.end > decoded_len {
+            return Err(Error::RangeOutOfBounds {
+                start: range.start,
+                end: range.end,
+                len: decoded_len,
+            });
+        }
+        if range.is_empty() {
+            return Ok(0);
+        }
+
+        let mut scratch = self.scratch.get();
+        scratch.clear();
 
-        for factor in EncodedFactorIterator::new(&mut scratch, &self.config) {
-            match factor {
-                FactorType::Literal(literal) => {
-                    output.write_all(&literal)?;
+        let decode_output = self.decompress(dict, input, &mut scratch).and_then(|()| {
+            let offset_bytes = self.config.index_width.offset_bytes(dict.len());
+            let mut factors = EncodedFactorIterator::new(&mut scratch, &self.config, offset_bytes);
+            let mut position = 0;
+            while position < range.end {
+                let len = factors.peek_len()? as usize;
+                if position + len <= range.start {
+                    factors.skip_factor()?;
+                    position += len;
+                    continue;
                 }
-                FactorType::Copy { offset, len } => {
-                    let offset = offset as usize;
-                    let dict_slice = &dict[offset..offset + len as usize];
-                    output.write_all(dict_slice)?;
+                let start = range.start.saturating_sub(position);
+                let end = len.min(range.end - position);
+                match factors.next_factor()? {
+                    FactorType::Literal(literal) => {
+                        output.write_all(&literal[start..end])?;
+                    }
+                    FactorType::Copy { offset, len } => {
+                        output.write_all(&dict[copy_range(dict, offset, len)?][start..end])?;
+                    }
                 }
+                position += len;
+            }
+            Ok(range.len())
+        });
+
+        self.scratch.release(scratch);
+        decode_output
+    }
+
+    /// Lengths of the factors of the document in `input` in order
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn factor_lens(
+        &self,
+        dict: &dict::Dictionary,
+        fingerprint: frame::Fingerprint,
+        mut input: &[u8],
+    ) -> Result<Vec<u32>, Error> {
+        let header = frame::FrameHeader::read(&mut input)?;
+        header.check(fingerprint)?;
+
+        let mut scratch = self.scratch.get();
+        scratch.clear();
+        let lens = self.decompress(dict, input, &mut scratch).map(|()| {
+            let mut lens = &scratch.lens[..];
+            let mut factor_lens = Vec::with_capacity(lens.len() / std::mem::size_of::<u32>());
+            while lens.remaining() >= std::mem::size_of::<u32>() {
+                factor_lens.push(lens.get_u32());
+            }
+            factor_lens
+        });
+        self.scratch.release(scratch);
+        lens
+    }
+
+    /// Decompress the streams of the encoded document `input` into `scratch`
+    fn decompress(
+        &self,
+        dict: &dict::Dictionary,
+        input: &[u8],
+        scratch: &mut scratch::Scratch,
+    ) -> Result<(), Error> {
+        let offset_bytes = self.config.index_width.offset_bytes(dict.len());
+        self.coder.decode(input, scratch, offset_bytes)
+    }
+
+    /// Decompress the streams of `input` to decode its factors one at a time
+    pub(crate) fn factor_reader(
+        &self,
+        dict: &dict::Dictionary,
+        input: &[u8],
+    ) -> Result<FactorReader<'_>, Error> {
+        let mut scratch = self.scratch.get();
+        scratch.clear();
+        if let Err(error) = self.decompress(dict, input, &mut scratch) {
+            self.scratch.release(scratch);
+            return Err(error);
+        }
+        Ok(FactorReader {
+            decoder: self,
+            scratch: Some(scratch),
+            offset_bytes: self.config.index_width.offset_bytes(dict.len()),
+        })
+    }
+}
+
+/// Dictionary range of a copy factor
+fn copy_range(
+    dict: &dict::Dictionary,
+    offset: usize,
+    len: u32,
+) -> Result<std::ops::Range<usize>, Error> {
+    offset
+        .checked_add(len as usize)
+        .filter(|end| *end <= dict.len())
+        .map(|end| offset..end)
+        .ok_or(Error::CopyOutOfRange {
+            offset,
+            len,
+            dict_len: dict.len(),
+        })
+}
+
+/// Decompressed streams of a document whose factors are decoded on demand
+pub(crate) struct FactorReader<'decoder> {
+    decoder: &'decoder Decoder,
+    scratch: Option<scratch::Scratch>,
+    offset_bytes: usize,
+}
+
+impl FactorReader<'_> {
+    /// Decoded bytes of the next factor, `None` once all factors were read
+    pub(crate) fn next_bytes(
+        &mut self,
+        dict: &dict::Dictionary,
+    ) -> Result<Option<bytes::Bytes>, Error> {
+        let Some(scratch) = self.scratch.as_mut() else {
+            return Ok(None);
+        };
+        let mut factors =
+            EncodedFactorIterator::new(scratch, &self.decoder.config, self.offset_bytes);
+        match factors.next().transpose()? {
+            Some(FactorType::Literal(literal)) => Ok(Some(literal)),
+            Some(FactorType::Copy { offset, len }) => {
+                Ok(Some(dict.slice(copy_range(dict, offset, len)?)))
+            }
+            None => {
+                factors.check_exhausted()?;
+                Ok(None)
             }
         }
+    }
+}
 
-        Ok(0)
+impl Drop for FactorReader<'_> {
+    fn drop(&mut self) {
+        if let Some(scratch) = self.scratch.take() {
+            self.decoder.scratch.release(scratch);
+        }
     }
 }
 
 struct EncodedFactorIterator<'scratch, 'decoder> {
     scratch: &'scratch mut scratch::Scratch,
     config: &'decoder config::Configuration,
+    offset_bytes: usize,
 }
 
 impl<'scratch, 'decoder> EncodedFactorIterator<'scratch, 'decoder> {
     fn new(
         scratch: &'scratch mut scratch::Scratch,
         config: &'decoder config::Configuration,
+        offset_bytes: usize,
     ) -> Self {
-        Self { scratch, config }
+        Self {
+            scratch,
+            config,
+            offset_bytes,
+        }
+    }
+
+    /// Once all lengths are consumed the literal and offset streams must be consumed too
+    fn check_exhausted(&self) -> Result<(), Error> {
+        if self.scratch.lens.has_remaining() {
+            return Ok(());
+        }
+        if self.scratch.literals.has_remaining() {
+            return Err(Error::FactorCountMismatch {
+                stream: coder::Stream::Literals,
+            });
+        }
+        if self.scratch.offsets.has_remaining() {
+            return Err(Error::FactorCountMismatch {
+                stream: coder::Stream::Offsets,
+            });
+        }
+        Ok(())
+    }
+
+    /// Sum of the lengths of all remaining factors
+    fn total_len(&self) -> Result<u64, Error> {
+        let lens = self.scratch.lens.chunks_exact(std::mem::size_of::<u32>());
+        if !lens.remainder().is_empty() {
+            return Err(Error::TruncatedStream {
+                stream: coder::Stream::Lens,
+            });
+        }
+        Ok(lens.map(|mut len| u64::from(len.get_u32())).sum())
+    }
+
+    /// Length of the next factor without consuming it
+    fn peek_len(&self) -> Result<u32, Error> {
+        match self.scratch.lens.get(..std::mem::size_of::<u32>()) {
+            Some(len) => Ok((&*len).get_u32()),
+            None => Err(Error::TruncatedStream {
+                stream: coder::Stream::Lens,
+            }),
+        }
+    }
+
+    /// Consume the next factor without decoding its bytes
+    fn skip_factor(&mut self) -> Result<(), Error> {
+        let truncated = |stream| Error::TruncatedStream { stream };
+        let len = self.peek_len()?;
+        self.scratch.lens.advance(std::mem::size_of::<u32>());
+        if len <= self.config.literal_threshold {
+            if self.scratch.literals.remaining() < len as usize {
+                return Err(truncated(coder::Stream::Literals));
+            }
+            self.scratch.literals.advance(len as usize);
+        } else {
+            if self.scratch.offsets.remaining() < self.offset_bytes {
+                return Err(truncated(coder::Stream::Offsets));
+            }
+            self.scratch.offsets.advance(self.offset_bytes);
+        }
+        Ok(())
+    }
+
+    fn next_factor(&mut self) -> Result<FactorType, Error> {
+        let truncated = |stream| Error::TruncatedStream { stream };
+        if self.scratch.lens.remaining() < std::mem::size_of::<u32>() {
+            return Err(truncated(coder::Stream::Lens));
+        }
+        let len = self.scratch.lens.get_u32();
+        if len <= self.config.literal_threshold {
+            if self.scratch.literals.remaining() < len as usize {
+                return Err(truncated(coder::Stream::Literals));
+            }
+            let literal_slice = self.scratch.literals.copy_to_bytes(len as usize);
+            Ok(FactorType::Literal(literal_slice))
+        } else {
+            if self.scratch.offsets.remaining() < self.offset_bytes {
+                return Err(truncated(coder::Stream::Offsets));
+            }
+            let offset = self.scratch.offsets.get_uint(self.offset_bytes);
+            let offset = usize::try_from(offset).unwrap_or(usize::MAX);
+            Ok(FactorType::Copy { offset, len })
+        }
     }
 }
 
-impl<'scratch, 'decoder> Iterator for EncodedFactorIterator<'scratch, 'decoder> {
-    type Item = FactorType;
+impl Iterator for EncodedFactorIterator<'_, '_> {
+    type Item = Result<FactorType, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let remaining = self.scratch.lens.has_remaining();
-        if remaining {
-            let len = self.scratch.lens.get_u32();
-            if len <= self.config.literal_threshold {
-                let literal_slice = self.scratch.literals.copy_to_bytes(len as usize);
-                Some(FactorType::Literal(literal_slice))
-            } else {
-                let offset = self.scratch.offsets.get_u32();
-                Some(FactorType::Copy { offset, len })
-            }
+        if self.scratch.lens.has_remaining() {
+            Some(self.next_factor())
         } else {
             None
         }
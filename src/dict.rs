@@ -1,18 +1,82 @@
 use bytes::Bytes;
+use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
 use std::ops::Deref;
+mod analysis;
+mod pruning;
 mod reservoir;
 mod stratified;
+mod usage;
 
+pub use analysis::{BlockUsage, DictionaryAnalyzer, DictionaryReport};
+pub use pruning::PruningDictionaryBuilder;
 pub use reservoir::ReservoirDictionaryBuilder;
 pub use stratified::StratifiedReservoirDictionaryBuilder;
 
+/// Generator of the reservoir builders seeded from a user supplied `rng`
+fn seeded_rng(mut rng: impl rand::RngCore) -> rand_chacha::ChaCha8Rng {
+    let mut seed = <rand_chacha::ChaCha8Rng as rand::SeedableRng>::Seed::default();
+    rng.fill_bytes(&mut seed);
+    rand::SeedableRng::from_seed(seed)
+}
+
+/// Number of successes when drawing `draws` out of `population` items without replacement,
+/// `successes` of which are successes
+fn hypergeometric(
+    population: usize,
+    successes: usize,
+    draws: usize,
+    rng: &mut impl rand::Rng,
+) -> usize {
+    let (mut population, mut successes) = (population, successes);
+    let mut hits = 0;
+    for _ in 0..draws.min(population) {
+        if rng.gen_range(0..population) < successes {
+            successes -= 1;
+            hits += 1;
+        }
+        population -= 1;
+    }
+    hits
+}
+
+/// Merge `other`, a reservoir over `other_seen` chunks, into `reservoir`, a reservoir over
+/// `seen` chunks of a disjoint input. The number of samples kept from `other` follows the
+/// hypergeometric distribution of drawing the merged reservoir from all `seen + other_seen`
+/// chunks, and the kept samples are uniform subsets of both reservoirs, so the result is a
+/// uniform sample of both inputs. Empty slots are filled from the other reservoir.
+fn merge_reservoirs(
+    reservoir: &mut [Option<Bytes>],
+    other: Vec<Option<Bytes>>,
+    seen: usize,
+    other_seen: usize,
+    rng: &mut impl rand::Rng,
+) {
+    let mut samples: Vec<Bytes> = reservoir.iter_mut().filter_map(Option::take).collect();
+    let mut other_samples: Vec<Bytes> = other.into_iter().flatten().collect();
+    let merged_len = reservoir.len().min(samples.len() + other_samples.len());
+    // a reservoir can hold fewer samples than the chunks it has seen
+    let from_other = hypergeometric(seen + other_seen, other_seen, merged_len, rng).clamp(
+        merged_len - samples.len().min(merged_len),
+        other_samples.len(),
+    );
+
+    let (from_other, _) = other_samples.partial_shuffle(rng, from_other);
+    let (from_self, _) = samples.partial_shuffle(rng, merged_len - from_other.len());
+    let mut merged: Vec<Bytes> = from_self.iter().chain(from_other.iter()).cloned().collect();
+    merged.shuffle(rng);
+    for (slot, sample) in reservoir.iter_mut().zip(merged) {
+        *slot = Some(sample);
+    }
+}
+
 /// Dictionary used for RLZ compression
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Dictionary(Bytes);
 
 impl Dictionary {
-    /// reservoir sample based dictionary builder
+    /// reservoir sample based dictionary builder, see [`ReservoirDictionaryBuilder::seed`] for
+    /// reproducible dictionaries
     #[must_use]
     pub fn reservoir_builder(
         dict_mib: usize,
@@ -22,7 +86,8 @@ impl Dictionary {
         ReservoirDictionaryBuilder::empty(dict_mib, sample_size, reservoir_mib)
     }
 
-    /// stratified reservoir sample based dictionary builder
+    /// stratified reservoir sample based dictionary builder, see
+    /// [`StratifiedReservoirDictionaryBuilder::seed`] for reproducible dictionaries
     #[must_use]
     pub fn stratified_reservoir_builder(
         dict_mib: usize,
@@ -32,6 +97,19 @@ impl Dictionary {
         StratifiedReservoirDictionaryBuilder::empty(dict_mib, sample_size, items_per_bucket)
     }
 
+    /// builder which shrinks this dictionary to `dict_mib` by keeping the segments of
+    /// `segment_size` bytes most referenced when factorizing sample documents
+    #[must_use]
+    pub fn pruning_builder(self, dict_mib: usize, segment_size: usize) -> PruningDictionaryBuilder {
+        PruningDictionaryBuilder::new(self, dict_mib * 1024 * 1024, segment_size)
+    }
+
+    /// metrohash of the dictionary content, identifies the dictionary in encoded documents
+    #[must_use]
+    pub fn fingerprint(&self) -> u64 {
+        crate::frame::metrohash64(&self.0)
+    }
+
     /// Construct dictionary from existing bytes
     pub fn from(mut bytes: impl bytes::Buf) -> Self {
         Self(bytes.copy_to_bytes(bytes.remaining()))
@@ -45,3 +123,37 @@ impl Deref for Dictionary {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    #[allow(clippy::cast_precision_loss)]
+    fn merge_split_is_hypergeometric() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+        let reservoir = |symbol: u8| vec![Some(Bytes::from(vec![symbol])); 8];
+        let trials = 20_000usize;
+        let mut counts = [0usize; 9];
+        for _ in 0..trials {
+            let mut merged = reservoir(b'a');
+            merge_reservoirs(&mut merged, reservoir(b'b'), 24, 8, &mut rng);
+            let from_other = merged.iter().flatten().filter(|s| s[0] == b'b').count();
+            assert_eq!(merged.iter().flatten().count(), 8);
+            counts[from_other] += 1;
+        }
+        let mean =
+            counts.iter().enumerate().map(|(x, c)| x * c).sum::<usize>() as f64 / trials as f64;
+        let variance = counts
+            .iter()
+            .enumerate()
+            .map(|(x, c)| (x as f64 - mean).powi(2) * *c as f64)
+            .sum::<f64>()
+            / trials as f64;
+        // drawing 8 of 32 chunks, 8 of which were seen by `other`: mean 2, variance 1.16.
+        // Flipping a weighted coin per slot would be binomial with variance 1.5.
+        assert!((mean - 2.0).abs() < 0.05, "mean {mean}");
+        assert!((variance - 1.161).abs() < 0.08, "variance {variance}");
+    }
+}
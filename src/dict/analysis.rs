@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+use super::usage::SegmentUsage;
+use crate::{factor::FactorType, Error, RlzCompressor};
+
+/// Accumulates how often and how many bytes each block of the dictionary of a compressor is
+/// referenced by copy factors when factorizing sample documents
+#[allow(clippy::module_name_repetitions)]
+pub struct DictionaryAnalyzer<'c> {
+    compressor: &'c RlzCompressor,
+    usage: SegmentUsage,
+    documents: usize,
+    sampled_bytes: u64,
+}
+
+impl<'c> DictionaryAnalyzer<'c> {
+    /// Analyze the dictionary of `compressor` in blocks of `block_size` bytes. Pass the sample
+    /// size the dictionary was built with to judge every sample on its own.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no encoder is available to factorize documents
+    pub fn new(compressor: &'c RlzCompressor, block_size: usize) -> Result<Self, Error> {
+        if compressor.encoder.is_none() {
+            return Err(Error::NoEncoderAvailable);
+        }
+        Ok(Self {
+            compressor,
+            usage: SegmentUsage::new(compressor.dict.len(), block_size),
+            documents: 0,
+            sampled_bytes: 0,
+        })
+    }
+
+    /// Factorize a sample document and credit every block with the copies it serves
+    #[tracing::instrument(skip_all)]
+    pub fn sample(&mut self, document: &[u8]) {
+        let Some(encoder) = &self.compressor.encoder else {
+            return;
+        };
+        for factor in encoder.index.factorize(&self.compressor.dict, document) {
+            if let FactorType::Copy { offset, len } = factor {
+                self.usage.record_copy(offset, len as usize);
+            }
+        }
+        self.documents += 1;
+        self.sampled_bytes += document.len() as u64;
+    }
+
+    /// Usage of every block of the dictionary over all sampled documents
+    #[must_use]
+    pub fn report(&self) -> DictionaryReport {
+        let blocks = (0..self.usage.len())
+            .map(|block| BlockUsage {
+                range: self.usage.segment_start(block)..self.usage.segment_end(block),
+                references: self.usage.references[block],
+                bytes_served: self.usage.bytes_served[block],
+            })
+            .collect();
+        DictionaryReport {
+            block_size: self.usage.segment_size,
+            documents: self.documents,
+            sampled_bytes: self.sampled_bytes,
+            blocks,
+        }
+    }
+}
+
+/// Usage of a block of the dictionary
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct BlockUsage {
+    /// dictionary range of the block
+    pub range: Range<usize>,
+    /// number of copy factors overlapping the block
+    pub references: u64,
+    /// bytes of copy factors served from the block
+    pub bytes_served: u64,
+}
+
+impl BlockUsage {
+    /// Whether the block served at least as many bytes as it occupies in the dictionary
+    #[must_use]
+    pub fn pays_for_itself(&self) -> bool {
+        self.bytes_served >= self.range.len() as u64
+    }
+}
+
+/// Per block usage of a dictionary over a sample collection, see [`DictionaryAnalyzer`]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct DictionaryReport {
+    /// bytes per block, the last block may be shorter
+    pub block_size: usize,
+    /// number of sampled documents
+    pub documents: usize,
+    /// bytes of all sampled documents
+    pub sampled_bytes: u64,
+    /// usage of all blocks in dictionary order
+    pub blocks: Vec<BlockUsage>,
+}
+
+impl DictionaryReport {
+    /// The `count` blocks serving the most bytes, most used first
+    #[must_use]
+    pub fn hot_blocks(&self, count: usize) -> Vec<&BlockUsage> {
+        let mut blocks: Vec<&BlockUsage> = self.blocks.iter().collect();
+        blocks.sort_by_key(|block| std::cmp::Reverse(block.bytes_served));
+        blocks.truncate(count);
+        blocks
+    }
+
+    /// Maximal dictionary ranges never referenced by any copy factor
+    #[must_use]
+    pub fn cold_regions(&self) -> Vec<Range<usize>> {
+        let mut regions: Vec<Range<usize>> = Vec::new();
+        for block in self.blocks.iter().filter(|block| block.references == 0) {
+            match regions.last_mut() {
+                Some(region) if region.end == block.range.start => region.end = block.range.end,
+                _ => regions.push(block.range.clone()),
+            }
+        }
+        regions
+    }
+
+    /// Blocks which served fewer bytes than they occupy in the dictionary, candidates for
+    /// replacement when rebuilding the dictionary
+    #[must_use]
+    pub fn dead_weight(&self) -> Vec<&BlockUsage> {
+        self.blocks
+            .iter()
+            .filter(|block| !block.pays_for_itself())
+            .collect()
+    }
+
+    /// Dictionary bytes in blocks which do not pay for themselves
+    #[must_use]
+    pub fn dead_weight_bytes(&self) -> usize {
+        self.dead_weight()
+            .iter()
+            .map(|block| block.range.len())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Dictionary;
+
+    #[test]
+    fn report_hot_and_cold_blocks() {
+        let dict = Dictionary::from(&b"0123456789abcdefghijklmnopqrstABCDEFGHIJuvwxyz!@#$"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let mut analyzer = DictionaryAnalyzer::new(&rlz_compressor, 10).unwrap();
+        analyzer.sample(b"abcdefghij__ABCDEFGHIJ__abcdefghij");
+        analyzer.sample(b"ABCDEFGHIJ");
+        let report = analyzer.report();
+
+        assert_eq!(report.documents, 2);
+        assert_eq!(report.blocks.len(), 5);
+        assert_eq!(report.blocks[1].references, 2);
+        assert_eq!(report.blocks[1].bytes_served, 20);
+        assert_eq!(report.hot_blocks(1)[0].range, 10..20);
+        assert_eq!(report.cold_regions(), vec![0..10, 20..30, 40..50]);
+        assert_eq!(report.dead_weight_bytes(), 30);
+        assert!(report.blocks[3].pays_for_itself());
+    }
+
+    #[test]
+    fn require_encoder() {
+        let dict = Dictionary::from(&b"banana"[..]);
+        let mut stored = Vec::new();
+        RlzCompressor::builder()
+            .build_from_dict(dict)
+            .store(&mut stored)
+            .unwrap();
+        let loaded = RlzCompressor::load(&stored[..]).unwrap();
+        assert!(DictionaryAnalyzer::new(&loaded, 10).is_err());
+    }
+}
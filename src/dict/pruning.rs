@@ -0,0 +1,96 @@
+use bytes::{BufMut, BytesMut};
+
+use super::usage::SegmentUsage;
+use crate::{config, factor::FactorType, index, Dictionary};
+
+/// Shrinks an existing dictionary to the segments most used when factorizing sample documents
+#[allow(clippy::module_name_repetitions)]
+pub struct PruningDictionaryBuilder {
+    dict: Dictionary,
+    index: index::Index,
+    dict_size: usize,
+    usage: SegmentUsage,
+}
+
+impl PruningDictionaryBuilder {
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn new(dict: Dictionary, dict_size: usize, segment_size: usize) -> Self {
+        let index = index::Index::from_dict(&dict, &config::Configuration::default());
+        let usage = SegmentUsage::new(dict.len(), segment_size);
+        Self {
+            dict,
+            index,
+            dict_size,
+            usage,
+        }
+    }
+
+    /// factorize a sample document and credit every dictionary segment with the bytes it serves
+    #[tracing::instrument(skip_all)]
+    pub fn sample(&mut self, document: &[u8]) {
+        for factor in self.index.factorize(&self.dict, document) {
+            if let FactorType::Copy { offset, len } = factor {
+                self.usage.record_copy(offset, len as usize);
+            }
+        }
+    }
+
+    /// finish pruning and create a dictionary from the most used segments in their original order
+    #[tracing::instrument(skip_all)]
+    pub fn finish(self) -> Dictionary {
+        let mut by_usage: Vec<usize> = (0..self.usage.len()).collect();
+        by_usage.sort_by_key(|&segment| std::cmp::Reverse(self.usage.bytes_served[segment]));
+
+        let mut kept = Vec::new();
+        let mut kept_bytes = 0;
+        for segment in by_usage {
+            let segment_len = self.segment(segment).len();
+            if kept_bytes + segment_len > self.dict_size {
+                continue;
+            }
+            kept_bytes += segment_len;
+            kept.push(segment);
+        }
+        kept.sort_unstable();
+
+        let mut final_dict = BytesMut::with_capacity(kept_bytes);
+        for segment in kept {
+            final_dict.put_slice(self.segment(segment));
+        }
+        tracing::info!(
+            "pruned dictionary from {} to {} bytes",
+            self.dict.len(),
+            final_dict.len()
+        );
+        super::Dictionary(final_dict.freeze())
+    }
+
+    fn segment(&self, segment: usize) -> &[u8] {
+        &self.dict[self.usage.segment_start(segment)..self.usage.segment_end(segment)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_used_segments() {
+        let dict = Dictionary::from(&b"0123456789abcdefghijklmnopqrstABCDEFGHIJuvwxyz!@#$"[..]);
+        let mut builder = PruningDictionaryBuilder::new(dict, 20, 10);
+        builder.sample(b"abcdefghij__ABCDEFGHIJ__abcdefghij");
+        builder.sample(b"ABCDEFGHIJ");
+        let pruned = builder.finish();
+        assert_eq!(&pruned[..], b"abcdefghijABCDEFGHIJ");
+    }
+
+    #[test]
+    fn credit_overlapping_segments() {
+        let dict = Dictionary::from(&b"0123456789abcdefghij"[..]);
+        let mut builder = PruningDictionaryBuilder::new(dict, 10, 10);
+        builder.sample(b"789abcdefg");
+        assert_eq!(builder.usage.bytes_served, vec![3, 7]);
+        assert_eq!(builder.usage.references, vec![1, 1]);
+        assert_eq!(&builder.finish()[..], b"abcdefghij");
+    }
+}
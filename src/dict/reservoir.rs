@@ -1,17 +1,35 @@
 use bytes::{BufMut, Bytes, BytesMut};
 use rand::seq::SliceRandom;
-use rand::Rng;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 
-/// reservoir based dictionary construction
-///
-#[derive(Default)]
-#[allow(clippy::module_name_repetitions)]
+use crate::Error;
 
+/// reservoir based dictionary construction. Builders sampling disjoint parts of the input,
+/// on other threads or in other processes with their state serialized, are combined with
+/// [`ReservoirDictionaryBuilder::merge`].
+#[derive(Serialize, Deserialize)]
+#[allow(clippy::module_name_repetitions)]
 pub struct ReservoirDictionaryBuilder {
     dict_size: usize,
     sample_size: usize,
     itr: usize,
     samples: Vec<Option<Bytes>>,
+    rng: ChaCha8Rng,
+}
+
+impl Default for ReservoirDictionaryBuilder {
+    /// empty builder drawing samples with a generator seeded from the operating system
+    fn default() -> Self {
+        Self {
+            dict_size: 0,
+            sample_size: 0,
+            itr: 0,
+            samples: Vec::new(),
+            rng: ChaCha8Rng::from_entropy(),
+        }
+    }
 }
 
 impl ReservoirDictionaryBuilder {
@@ -23,15 +41,59 @@ impl ReservoirDictionaryBuilder {
             sample_size,
             itr: reservoir_size,
             samples: vec![None; reservoir_size],
+            rng: ChaCha8Rng::from_entropy(),
+        }
+    }
+
+    /// draw samples with a generator seeded by `seed`, so sampling the same input in the same
+    /// order builds a byte-identical dictionary
+    #[must_use]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self
+    }
+
+    /// draw samples with a generator seeded from `rng`
+    #[must_use]
+    pub fn rng(mut self, rng: impl RngCore) -> Self {
+        self.rng = super::seeded_rng(rng);
+        self
+    }
+
+    /// merge the reservoir of `other`, which sampled a disjoint part of the input, so the
+    /// result is a uniform sample of both inputs as if they were sampled by one builder.
+    /// Seed shards differently, builders with the same seed draw the same random numbers.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the builders were created with different parameters
+    #[tracing::instrument(skip_all)]
+    pub fn merge(&mut self, other: ReservoirDictionaryBuilder) -> Result<(), Error> {
+        if self.dict_size != other.dict_size
+            || self.sample_size != other.sample_size
+            || self.samples.len() != other.samples.len()
+        {
+            return Err(Error::IncompatibleBuilders);
         }
+        // `itr` starts at the reservoir size
+        let seen = self.itr.saturating_sub(self.samples.len());
+        let other_seen = other.itr.saturating_sub(other.samples.len());
+        super::merge_reservoirs(
+            &mut self.samples,
+            other.samples,
+            seen,
+            other_seen,
+            &mut self.rng,
+        );
+        self.itr += other_seen;
+        Ok(())
     }
 
     #[tracing::instrument(skip_all)]
     pub(crate) fn freeze(mut self, size_in_bytes: usize) -> Bytes {
-        let mut rng = rand::thread_rng();
-        self.samples.shuffle(&mut rng);
+        self.samples.shuffle(&mut self.rng);
         let mut final_dict = BytesMut::with_capacity(size_in_bytes);
-        for sample in self.samples.into_iter().filter_map(|l| l) {
+        for sample in self.samples.into_iter().flatten() {
             final_dict.put_slice(&sample);
             if final_dict.len() == size_in_bytes {
                 break;
@@ -50,9 +112,8 @@ impl ReservoirDictionaryBuilder {
     /// sample from a slice of new bytes
     #[tracing::instrument(skip_all)]
     pub fn sample(&mut self, new_bytes: &[u8]) {
-        let mut rng = rand::thread_rng();
         for sample in new_bytes.chunks(self.sample_size) {
-            let random_number = rng.gen_range(0..self.itr);
+            let random_number = self.rng.gen_range(0..self.itr);
             if random_number < self.samples.len() {
                 self.samples[random_number] = Some(Bytes::copy_from_slice(sample));
             }
@@ -60,3 +121,80 @@ impl ReservoirDictionaryBuilder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn build(seed: u64, documents: &[Vec<u8>]) -> Bytes {
+        let mut builder = ReservoirDictionaryBuilder::empty(1, 16, 1).seed(seed);
+        for document in documents {
+            builder.sample(document);
+        }
+        builder.freeze(256)
+    }
+
+    proptest! {
+        #[test]
+        fn same_seed_same_dictionary(documents: Vec<Vec<u8>>, seed: u64) {
+            assert_eq!(build(seed, &documents), build(seed, &documents));
+        }
+    }
+
+    #[test]
+    fn seed_changes_dictionary() {
+        let documents = vec![(0..=255).cycle().take(64 * 1024).collect::<Vec<u8>>()];
+        assert_ne!(build(1, &documents), build(2, &documents));
+
+        let from_rng = |seed| {
+            let mut builder =
+                ReservoirDictionaryBuilder::empty(1, 16, 1).rng(ChaCha8Rng::seed_from_u64(seed));
+            builder.sample(&documents[0]);
+            builder.freeze(256)
+        };
+        assert_eq!(from_rng(3), from_rng(3));
+    }
+
+    #[test]
+    fn default_builder_is_empty() {
+        assert!(ReservoirDictionaryBuilder::default().finish().is_empty());
+        assert!(ReservoirDictionaryBuilder::default()
+            .seed(1)
+            .freeze(16)
+            .is_empty());
+    }
+
+    #[test]
+    #[allow(clippy::naive_bytecount)]
+    fn merge_weighs_by_seen_chunks() {
+        let mut builder = ReservoirDictionaryBuilder::empty(1, 1, 1).seed(1);
+        let mut other = ReservoirDictionaryBuilder::empty(1, 1, 1).seed(2);
+        builder.sample(&vec![b'a'; 3 * 1024 * 1024]);
+        other.sample(&vec![b'b'; 1024 * 1024]);
+
+        let other: ReservoirDictionaryBuilder =
+            bincode::deserialize(&bincode::serialize(&other).unwrap()).unwrap();
+        builder.merge(other).unwrap();
+        assert_eq!(builder.itr, 5 * 1024 * 1024);
+
+        let dict = builder.freeze(1024 * 1024);
+        // a quarter of the merged input was sampled by `other`
+        let count = dict.iter().filter(|byte| **byte == b'b').count();
+        let percent = count * 100 / dict.len();
+        assert!((24..=25).contains(&percent), "{percent}% samples of b");
+    }
+
+    #[test]
+    fn merge_fills_empty_slots() {
+        let mut builder = ReservoirDictionaryBuilder::empty(1, 16, 1).seed(1);
+        let mut other = ReservoirDictionaryBuilder::empty(1, 16, 1).seed(2);
+        other.sample(b"banana");
+        builder.merge(other).unwrap();
+        assert_eq!(&builder.freeze(16)[..], b"banana");
+
+        let incompatible = ReservoirDictionaryBuilder::empty(1, 32, 1);
+        let mut builder = ReservoirDictionaryBuilder::empty(1, 16, 1);
+        assert!(builder.merge(incompatible).is_err());
+    }
+}
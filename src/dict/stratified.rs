@@ -1,18 +1,38 @@
 use bytes::{BufMut, Bytes, BytesMut};
-use rand::Rng;
-use std::collections::HashMap;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::hash::Hasher;
 
-/// stratified reservoir based dictionary construction
-#[derive(Default)]
-#[allow(clippy::module_name_repetitions)]
+use crate::Error;
 
+/// stratified reservoir based dictionary construction. Builders sampling disjoint parts of
+/// the input are combined with [`StratifiedReservoirDictionaryBuilder::merge`].
+#[derive(Serialize, Deserialize)]
+#[allow(clippy::module_name_repetitions)]
 pub struct StratifiedReservoirDictionaryBuilder {
     dict_size: usize,
     sample_size: usize,
     items_per_bucket: usize,
-    itr: HashMap<u64, usize>,
-    samples: HashMap<u64, Vec<Option<Bytes>>>,
+    // ordered so that the dictionary does not depend on the hash map iteration order
+    itr: BTreeMap<u64, usize>,
+    samples: BTreeMap<u64, Vec<Option<Bytes>>>,
+    rng: ChaCha8Rng,
+}
+
+impl Default for StratifiedReservoirDictionaryBuilder {
+    /// empty builder drawing samples with a generator seeded from the operating system
+    fn default() -> Self {
+        Self {
+            dict_size: 0,
+            sample_size: 0,
+            items_per_bucket: 0,
+            itr: BTreeMap::new(),
+            samples: BTreeMap::new(),
+            rng: ChaCha8Rng::from_entropy(),
+        }
+    }
 }
 
 impl StratifiedReservoirDictionaryBuilder {
@@ -22,23 +42,74 @@ impl StratifiedReservoirDictionaryBuilder {
             dict_size: dict_mib * 1024 * 1024,
             sample_size,
             items_per_bucket,
-            itr: HashMap::new(),
-            samples: HashMap::new(),
+            itr: BTreeMap::new(),
+            samples: BTreeMap::new(),
+            rng: ChaCha8Rng::from_entropy(),
         }
     }
 
+    /// draw samples with a generator seeded by `seed`, so sampling the same input in the same
+    /// order builds a byte-identical dictionary
+    #[must_use]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self
+    }
+
+    /// draw samples with a generator seeded from `rng`
+    #[must_use]
+    pub fn rng(mut self, rng: impl RngCore) -> Self {
+        self.rng = super::seeded_rng(rng);
+        self
+    }
+
+    /// merge the reservoirs of `other`, which sampled a disjoint part of the input, so every
+    /// stratum is a uniform sample of both inputs as if they were sampled by one builder.
+    /// Seed shards differently, builders with the same seed draw the same random numbers.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the builders were created with different parameters
+    #[tracing::instrument(skip_all)]
+    pub fn merge(&mut self, other: StratifiedReservoirDictionaryBuilder) -> Result<(), Error> {
+        if self.dict_size != other.dict_size
+            || self.sample_size != other.sample_size
+            || self.items_per_bucket != other.items_per_bucket
+        {
+            return Err(Error::IncompatibleBuilders);
+        }
+        for (id, other_reservoir) in other.samples {
+            // `itr` of every stratum starts at the reservoir size
+            let other_itr = other.itr.get(&id).copied();
+            let other_seen = other_itr.map_or(0, |itr| itr.saturating_sub(self.items_per_bucket));
+            let itr = self.itr.entry(id).or_insert(self.items_per_bucket);
+            let seen = itr.saturating_sub(self.items_per_bucket);
+            match self.samples.get_mut(&id) {
+                Some(reservoir) => super::merge_reservoirs(
+                    reservoir,
+                    other_reservoir,
+                    seen,
+                    other_seen,
+                    &mut self.rng,
+                ),
+                None => {
+                    self.samples.insert(id, other_reservoir);
+                }
+            }
+            *itr += other_seen;
+        }
+        Ok(())
+    }
+
     #[tracing::instrument(skip_all)]
     pub(crate) fn freeze(self, size_in_bytes: usize) -> Bytes {
         let num_buckets = self.samples.len();
         let num_samples = self.dict_size / self.sample_size;
-        let samples_per_bucket = (num_samples / num_buckets).max(1);
+        // an empty builder has no buckets and freezes to an empty dictionary
+        let samples_per_bucket = (num_samples / num_buckets.max(1)).max(1);
         let mut final_dict = BytesMut::with_capacity(size_in_bytes);
         for reservoir in self.samples.into_values() {
-            for sample in reservoir
-                .into_iter()
-                .filter_map(|l| l)
-                .take(samples_per_bucket)
-            {
+            for sample in reservoir.into_iter().flatten().take(samples_per_bucket) {
                 final_dict.put_slice(&sample);
                 if final_dict.len() == size_in_bytes {
                     break;
@@ -62,22 +133,15 @@ impl StratifiedReservoirDictionaryBuilder {
         identifier.hash(&mut hasher);
         let id = hasher.finish();
 
-        let (reservoir, itr) = if let Some(reservoir) = self.samples.get_mut(&id) {
-            (reservoir, self.itr.get_mut(&id).unwrap())
-        } else {
-            let new_reservoir = vec![None; self.items_per_bucket];
-            let iter = self.items_per_bucket;
-            self.samples.insert(id, new_reservoir);
-            self.itr.insert(id, iter);
-            (
-                self.samples.get_mut(&id).unwrap(),
-                self.itr.get_mut(&id).unwrap(),
-            )
-        };
-
-        let mut rng = rand::thread_rng();
+        let items_per_bucket = self.items_per_bucket;
+        let reservoir = self
+            .samples
+            .entry(id)
+            .or_insert_with(|| vec![None; items_per_bucket]);
+        let itr = self.itr.entry(id).or_insert(items_per_bucket);
+
         for sample in new_bytes.chunks(self.sample_size) {
-            let random_number = rng.gen_range(0..*itr);
+            let random_number = self.rng.gen_range(0..*itr);
             if random_number < reservoir.len() {
                 reservoir[random_number] = Some(Bytes::copy_from_slice(sample));
             }
@@ -85,3 +149,49 @@ impl StratifiedReservoirDictionaryBuilder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn build(seed: u64, documents: &[(u8, Vec<u8>)]) -> Bytes {
+        let mut builder = StratifiedReservoirDictionaryBuilder::empty(1, 16, 4).seed(seed);
+        for (identifier, document) in documents {
+            builder.sample(identifier, document);
+        }
+        builder.freeze(1024)
+    }
+
+    proptest! {
+        #[test]
+        fn same_seed_same_dictionary(documents: Vec<(u8, Vec<u8>)>, seed: u64) {
+            assert_eq!(build(seed, &documents), build(seed, &documents));
+        }
+    }
+
+    #[test]
+    #[allow(clippy::naive_bytecount)]
+    fn merge_strata() {
+        let mut builder = StratifiedReservoirDictionaryBuilder::empty(1, 1, 1024).seed(1);
+        let mut other = StratifiedReservoirDictionaryBuilder::empty(1, 1, 1024).seed(2);
+        builder.sample("shared", &vec![b'a'; 3 * 1024 * 64]);
+        other.sample("shared", &vec![b'b'; 1024 * 64]);
+        other.sample("other", b"cc");
+
+        let other: StratifiedReservoirDictionaryBuilder =
+            bincode::deserialize(&bincode::serialize(&other).unwrap()).unwrap();
+        builder.merge(other).unwrap();
+        assert_eq!(builder.samples.len(), 2);
+
+        let dict = builder.freeze(4096);
+        let count = |symbol| dict.iter().filter(|byte| **byte == symbol).count();
+        assert_eq!(count(b'c'), 2);
+        assert!(
+            (192..=320).contains(&count(b'b')),
+            "{} samples of b",
+            count(b'b')
+        );
+        assert_eq!(count(b'a') + count(b'b'), 1024);
+    }
+}
@@ -0,0 +1,47 @@
+/// Bytes served by and references to fixed size segments of a dictionary
+#[derive(Clone, Debug)]
+pub(crate) struct SegmentUsage {
+    pub(crate) segment_size: usize,
+    pub(crate) dict_len: usize,
+    pub(crate) bytes_served: Vec<u64>,
+    pub(crate) references: Vec<u64>,
+}
+
+impl SegmentUsage {
+    pub(crate) fn new(dict_len: usize, segment_size: usize) -> Self {
+        let segment_size = segment_size.max(1);
+        let num_segments = dict_len.div_ceil(segment_size);
+        Self {
+            segment_size,
+            dict_len,
+            bytes_served: vec![0; num_segments],
+            references: vec![0; num_segments],
+        }
+    }
+
+    /// Credit every segment overlapping a copy of `len` bytes at `offset` with the bytes it serves
+    pub(crate) fn record_copy(&mut self, offset: usize, len: usize) {
+        let start = offset;
+        let end = start + len;
+        let mut segment = start / self.segment_size;
+        while segment * self.segment_size < end {
+            let overlap =
+                end.min(self.segment_end(segment)) - start.max(self.segment_start(segment));
+            self.bytes_served[segment] += overlap as u64;
+            self.references[segment] += 1;
+            segment += 1;
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.bytes_served.len()
+    }
+
+    pub(crate) fn segment_start(&self, segment: usize) -> usize {
+        segment * self.segment_size
+    }
+
+    pub(crate) fn segment_end(&self, segment: usize) -> usize {
+        (self.segment_start(segment) + self.segment_size).min(self.dict_len)
+    }
+}
@@ -1,10 +1,12 @@
 use bytes::{Buf, BufMut};
 
-use crate::{coder, config, dict, index, scratch, Error};
+use crate::{coder, config, dict, index, scratch, stats, Error};
 
 pub(crate) struct Encoder {
     pub(crate) index: index::Index,
     pub(crate) coder: coder::Coder,
+    /// bytes per offset in the offset stream
+    offset_bytes: usize,
     scratch: scratch::ScratchSpace,
 }
 
@@ -15,9 +17,18 @@ impl Encoder {
         compression_config: &config::Configuration,
     ) -> Encoder {
         let index = index::Index::from_dict(dict, compression_config);
+        Encoder::from_index(index, dict, compression_config)
+    }
+
+    pub(crate) fn from_index(
+        index: index::Index,
+        dict: &dict::Dictionary,
+        compression_config: &config::Configuration,
+    ) -> Encoder {
         Encoder {
             index,
             coder: compression_config.factor_compression.clone(),
+            offset_bytes: compression_config.index_width.offset_bytes(dict.len()),
             scratch: scratch::ScratchSpace::default(),
         }
     }
@@ -30,12 +41,58 @@ impl Encoder {
         output: impl BufMut,
     ) -> Result<usize, Error> {
         let mut scratch = self.scratch.get();
+        let encode_output = self.encode_with(dict, input, output, &mut scratch);
+        self.scratch.release(scratch);
+        encode_output
+    }
+
+    /// Encode `input` using the scratch space of the caller instead of the shared pool
+    pub(crate) fn encode_with(
+        &self,
+        dict: &dict::Dictionary,
+        input: impl Buf,
+        output: impl BufMut,
+        scratch: &mut scratch::Scratch,
+    ) -> Result<usize, Error> {
         scratch.clear();
-        for factor in self.index.factorize(dict, input) {
-            self.coder.store_factor(&mut scratch, factor);
-        }
-        let encode_output = self.coder.encode(output, &mut scratch);
+        self.factorize_into(dict, input, scratch, None);
+        self.coder.encode(output, scratch, self.offset_bytes)
+    }
+
+    /// Encode `input` and add its factors to `stats`
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn encode_with_stats(
+        &self,
+        dict: &dict::Dictionary,
+        input: impl Buf,
+        output: impl BufMut,
+        stats: &mut stats::EncodeStats,
+    ) -> Result<usize, Error> {
+        let mut scratch = self.scratch.get();
+        scratch.clear();
+        self.factorize_into(dict, input, &mut scratch, Some(stats));
+        let encode_output = self.coder.encode(output, &mut scratch, self.offset_bytes);
         self.scratch.release(scratch);
         encode_output
     }
+
+    /// Append the factors of `input` to the streams in `scratch` and return the number of factors
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn factorize_into(
+        &self,
+        dict: &dict::Dictionary,
+        input: impl Buf,
+        scratch: &mut scratch::Scratch,
+        mut stats: Option<&mut stats::EncodeStats>,
+    ) -> usize {
+        let mut num_factors = 0;
+        for factor in self.index.factorize(dict, input) {
+            if let Some(stats) = stats.as_mut() {
+                stats.add_factor(&factor);
+            }
+            self.coder.store_factor(scratch, factor, self.offset_bytes);
+            num_factors += 1;
+        }
+        num_factors
+    }
 }
@@ -1,5 +1,24 @@
 use thiserror::Error;
 
+use crate::coder;
+
+/// Part of an encoded document or a stored compressor which failed checksum verification
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ChecksumPart {
+    /// a compressed factor stream of an encoded document
+    Stream(coder::Stream),
+    /// the decoded document
+    Document,
+    /// the dictionary of a stored compressor
+    Dictionary,
+    /// the configuration of a stored compressor
+    Configuration,
+    /// the index of a stored compressor
+    Index,
+    /// the block directory of an archive
+    ArchiveDirectory,
+}
+
 /// RLZ Error type
 #[derive(Error, Debug)]
 pub enum Error {
@@ -27,4 +46,105 @@ pub enum Error {
     /// serialize/deserialize error of the rlz compressor
     #[error("Bincode serialization Error")]
     SerializeError(#[from] bincode::Error),
+    /// archive is truncated or not an archive at all
+    #[error("Invalid or corrupt archive")]
+    InvalidArchive,
+    /// memory mapped compressor is truncated or was written on a platform with different endianness
+    #[error("Invalid or corrupt memory mapped compressor")]
+    InvalidMappedLayout,
+    /// requested document is not part of the archive
+    #[error("Document {doc_id} not found in archive")]
+    DocumentNotFound {
+        /// requested document id
+        doc_id: usize,
+    },
+    /// input does not start with the header of an encoded document
+    #[error("Invalid or truncated encoded document header")]
+    InvalidFrame,
+    /// document was encoded with an unsupported version of the format
+    #[error("Unsupported encoded document version {version}")]
+    UnsupportedVersion {
+        /// format version of the document
+        version: u8,
+    },
+    /// document was encoded against a different dictionary
+    #[error("Dictionary mismatch: expected fingerprint {expected:#018x}, found {found:#018x}")]
+    DictionaryMismatch {
+        /// fingerprint of the dictionary used for decoding
+        expected: u64,
+        /// fingerprint of the dictionary the document was encoded against
+        found: u64,
+    },
+    /// document was encoded with a different literal threshold, index width or codecs
+    #[error("Configuration mismatch between encoder and decoder")]
+    ConfigurationMismatch,
+    /// encoded input ends in the middle of a header or a stream
+    #[error("Truncated encoded input")]
+    TruncatedInput,
+    /// a stream holds fewer values than the length stream references
+    #[error("{stream:?} stream is truncated")]
+    TruncatedStream {
+        /// truncated stream
+        stream: coder::Stream,
+    },
+    /// a stream holds more values than the length stream references
+    #[error("{stream:?} stream holds values not referenced by the length stream")]
+    FactorCountMismatch {
+        /// stream with unreferenced values
+        stream: coder::Stream,
+    },
+    /// a copy factor references bytes past the end of the dictionary
+    #[error("Copy of {len} bytes at offset {offset} exceeds dictionary of {dict_len} bytes")]
+    CopyOutOfRange {
+        /// dictionary offset of the copy
+        offset: usize,
+        /// length of the copy
+        len: u32,
+        /// length of the dictionary
+        dict_len: usize,
+    },
+    /// decoded document is shorter or longer than the length stored in its frame
+    #[error("Decoded {found} bytes but the frame stores a length of {expected} bytes")]
+    DecodedLengthMismatch {
+        /// length stored in the frame
+        expected: u64,
+        /// number of decoded bytes
+        found: u64,
+    },
+    /// output slice can not hold the decoded document
+    #[error("Output of {available} bytes is too small for {required} decoded bytes")]
+    OutputTooSmall {
+        /// decoded length of the document
+        required: usize,
+        /// length of the output slice
+        available: usize,
+    },
+    /// requested byte range is not part of the decoded document
+    #[error("Range {start}..{end} is out of bounds for document of {len} bytes")]
+    RangeOutOfBounds {
+        /// start of the requested range
+        start: usize,
+        /// end of the requested range
+        end: usize,
+        /// length of the decoded document
+        len: usize,
+    },
+    /// dictionary builders created with different parameters can not be merged
+    #[error("Dictionary builders with different parameters can not be merged")]
+    IncompatibleBuilders,
+    /// stored index does not belong to the stored dictionary
+    #[error("Stored index does not match the dictionary")]
+    InvalidIndex,
+    /// input does not start with the magic of an encoded stream
+    #[error("Input is not an encoded stream")]
+    InvalidStream,
+    /// stored checksum does not match the content
+    #[error("Checksum mismatch: {part:?} is corrupt")]
+    ChecksumMismatch {
+        /// corrupt part
+        part: ChecksumPart,
+    },
+    /// worker threads of a batch pool could not be spawned
+    #[error("Failed to spawn batch worker threads")]
+    ThreadPoolError(#[from] rayon::ThreadPoolBuildError),
 }
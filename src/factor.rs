@@ -4,7 +4,7 @@ use bytes::Bytes;
 
 pub(crate) enum FactorType {
     Literal(Bytes),
-    Copy { offset: u32, len: u32 },
+    Copy { offset: usize, len: u32 },
 }
 
 impl FactorType {
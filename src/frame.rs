@@ -0,0 +1,271 @@
+//! Header preceding every document encoded by [`crate::RlzCompressor::encode`]:
+//!
+//! ```text
+//! [magic: 4][version: u8][flags: u8][dict fingerprint: u64][config fingerprint: u32]
+//! [decoded len: u64][document checksum: u32 if flags & CHECKSUM]
+//! ```
+//!
+//! All integers are little endian. The encoded factor streams follow the header.
+use bytes::{Buf, BufMut};
+use std::hash::Hasher;
+
+use crate::{config, ChecksumPart, Error};
+
+const MAGIC: &[u8; 4] = b"RLZF";
+const VERSION: u8 = 1;
+const MIN_HEADER_BYTES: usize = MAGIC.len() + 1 + 1 + 8 + 4 + 8;
+/// flag set if the header stores a checksum of the decoded document
+const CHECKSUM: u8 = 1;
+
+/// Identifies the dictionary and the settings needed to decode a document
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) struct Fingerprint {
+    pub(crate) dict: u64,
+    pub(crate) config: u32,
+}
+
+impl Fingerprint {
+    /// Fingerprint of a dictionary with fingerprint `dict` and `config`. The dictionary
+    /// fingerprint is computed once when the compressor is built and stored with it, as
+    /// hashing a large dictionary on every load would read all of it.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn new(dict: u64, config: &config::Configuration) -> Self {
+        // only the settings the decoder depends on. serializing plain integers and
+        // enums to a vec can not fail
+        let decode_settings = bincode::serialize(&(
+            config.literal_threshold,
+            config.index_width,
+            &config.factor_compression,
+        ))
+        .unwrap_or_default();
+        Fingerprint {
+            dict,
+            config: metrohash64(&decode_settings) as u32,
+        }
+    }
+
+    /// Ensure this matches the dictionary and settings of `expected`
+    pub(crate) fn check(self, expected: Fingerprint) -> Result<(), Error> {
+        if self.dict != expected.dict {
+            return Err(Error::DictionaryMismatch {
+                expected: expected.dict,
+                found: self.dict,
+            });
+        }
+        if self.config != expected.config {
+            return Err(Error::ConfigurationMismatch);
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn metrohash64(bytes: &[u8]) -> u64 {
+    let mut hasher = metrohash::MetroHash64::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// Checksum of a compressed stream or decoded document
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn checksum(bytes: &[u8]) -> u32 {
+    metrohash64(bytes) as u32
+}
+
+/// Computes the checksum of everything written through it
+pub(crate) struct ChecksumWriter<W> {
+    inner: W,
+    hasher: metrohash::MetroHash64,
+}
+
+impl<W: std::io::Write> ChecksumWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        ChecksumWriter {
+            inner,
+            hasher: metrohash::MetroHash64::new(),
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn checksum(&self) -> u32 {
+        self.hasher.finish() as u32
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.write(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Computes the checksum of everything read through it
+pub(crate) struct ChecksumReader<R> {
+    inner: R,
+    hasher: metrohash::MetroHash64,
+}
+
+impl<R: std::io::Read> ChecksumReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        ChecksumReader {
+            inner,
+            hasher: metrohash::MetroHash64::new(),
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn checksum(&self) -> u32 {
+        self.hasher.finish() as u32
+    }
+}
+
+impl<R: std::io::Read> std::io::Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.write(&buf[..read]);
+        Ok(read)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) struct FrameHeader {
+    pub(crate) fingerprint: Fingerprint,
+    pub(crate) decoded_len: u64,
+    pub(crate) checksum: Option<u32>,
+}
+
+impl FrameHeader {
+    /// Bytes of the serialized header
+    pub(crate) fn len(&self) -> usize {
+        MIN_HEADER_BYTES + self.checksum.map_or(0, |_| 4)
+    }
+
+    pub(crate) fn write(&self, mut output: impl BufMut) -> usize {
+        output.put_slice(MAGIC);
+        output.put_u8(VERSION);
+        output.put_u8(if self.checksum.is_some() { CHECKSUM } else { 0 });
+        output.put_u64_le(self.fingerprint.dict);
+        output.put_u32_le(self.fingerprint.config);
+        output.put_u64_le(self.decoded_len);
+        if let Some(checksum) = self.checksum {
+            output.put_u32_le(checksum);
+        }
+        self.len()
+    }
+
+    /// Read the header from the start of `input` and advance `input` past it
+    pub(crate) fn read(input: &mut &[u8]) -> Result<Self, Error> {
+        if input.len() < MIN_HEADER_BYTES || &input[..MAGIC.len()] != MAGIC {
+            return Err(Error::InvalidFrame);
+        }
+        input.advance(MAGIC.len());
+        let version = input.get_u8();
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion { version });
+        }
+        let flags = input.get_u8();
+        let fingerprint = Fingerprint {
+            dict: input.get_u64_le(),
+            config: input.get_u32_le(),
+        };
+        let decoded_len = input.get_u64_le();
+        let checksum = if flags & CHECKSUM == 0 {
+            None
+        } else if input.len() < 4 {
+            return Err(Error::InvalidFrame);
+        } else {
+            Some(input.get_u32_le())
+        };
+        Ok(FrameHeader {
+            fingerprint,
+            decoded_len,
+            checksum,
+        })
+    }
+
+    /// Ensure `checksum` of the decoded document matches the stored checksum, if any
+    pub(crate) fn verify(&self, checksum: u32) -> Result<(), Error> {
+        match self.checksum {
+            Some(expected) if expected != checksum => Err(Error::ChecksumMismatch {
+                part: ChecksumPart::Document,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Ensure `decoded` bytes match the stored length of the document
+    pub(crate) fn verify_len(&self, decoded: usize) -> Result<(), Error> {
+        if self.decoded_len != decoded as u64 {
+            return Err(Error::DecodedLengthMismatch {
+                expected: self.decoded_len,
+                found: decoded as u64,
+            });
+        }
+        Ok(())
+    }
+
+    /// Ensure the document was encoded against the dictionary and settings of `expected`
+    pub(crate) fn check(&self, expected: Fingerprint) -> Result<(), Error> {
+        self.fingerprint.check(expected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn write_and_read(dict: u64, config: u32, decoded_len: u64, checksum: Option<u32>, payload: Vec<u8>) {
+            let header = FrameHeader {
+                fingerprint: Fingerprint { dict, config },
+                decoded_len,
+                checksum,
+            };
+            let mut output = Vec::new();
+            assert_eq!(header.write(&mut output), header.len());
+            assert_eq!(output.len(), header.len());
+            output.extend_from_slice(&payload);
+
+            let mut input = &output[..];
+            assert_eq!(FrameHeader::read(&mut input)?, header);
+            assert_eq!(input, &payload[..]);
+
+            for len in 0..header.len() {
+                assert!(FrameHeader::read(&mut &output[..len]).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn reject_unknown_version() {
+        let mut output = Vec::new();
+        FrameHeader {
+            fingerprint: Fingerprint { dict: 1, config: 2 },
+            decoded_len: 3,
+            checksum: None,
+        }
+        .write(&mut output);
+        output[MAGIC.len()] = VERSION + 1;
+        assert!(matches!(
+            FrameHeader::read(&mut &output[..]),
+            Err(Error::UnsupportedVersion { version }) if version == VERSION + 1
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn checksum_writer_matches(chunks: Vec<Vec<u8>>) {
+            let mut writer = ChecksumWriter::new(Vec::new());
+            for chunk in &chunks {
+                std::io::Write::write_all(&mut writer, chunk)?;
+            }
+            assert_eq!(writer.checksum(), checksum(&chunks.concat()));
+        }
+    }
+}
@@ -0,0 +1,23 @@
+//! Internals exposed to the fuzz targets in `fuzz/`. Not covered by semver.
+use crate::index::suffix_array::{SuffixArray, SuffixArrayMatch};
+
+/// Text positions of all occurrences of `pattern` in `text`, found by narrowing the
+/// suffix array range with `SuffixArray::refine_bounds` one pattern symbol at a time
+#[must_use]
+pub fn suffix_array_matches(text: &[u8], pattern: &[u8], wide: bool) -> Vec<usize> {
+    let sa = if wide {
+        SuffixArray::new_wide(text)
+    } else {
+        SuffixArray::new(text)
+    };
+    let (mut range, num_matched) = match sa.start_range_from_pattern(pattern) {
+        SuffixArrayMatch::NoMatch => return Vec::new(),
+        SuffixArrayMatch::Match { num_matched, range } => (range, num_matched),
+    };
+    for (offset, sym) in pattern.iter().enumerate().skip(num_matched) {
+        range = sa.refine_bounds(range, *sym, offset, text);
+    }
+    let mut positions = sa.positions_in(range);
+    positions.sort_unstable();
+    positions
+}
@@ -1,11 +1,16 @@
 use crate::factor::FactorType;
-use crate::{config, dict, Dictionary};
-mod suffix_array;
+use crate::{coder, config, dict, Dictionary};
+mod optimal;
+pub(crate) mod suffix_array;
 
 use bytes::Buf;
 use suffix_array::SuffixArray;
 
-use self::suffix_array::{SuffixArrayMatch, SuffixArrayRangeInclusive};
+use self::suffix_array::SuffixArrayMatch;
+pub(crate) use self::suffix_array::SuffixArrayRangeInclusive;
+
+/// Largest dictionary supported by [`config::IndexWidth::Compact`]
+pub(crate) const MAX_COMPACT_DICT_LEN: usize = suffix_array::MAX_LENGTH;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
@@ -14,30 +19,91 @@ pub(crate) struct Index {
     pub(crate) config: config::Configuration,
 }
 
+/// Number of positions `ParsingStrategy::Lazy` looks ahead for a longer match
+const LAZY_LOOKAHEAD: usize = 2;
+
+#[derive(Copy, Clone)]
 enum IndexSearchResult {
     NoMatch,
-    Match { num_matched: u32, offset: u32 },
+    Match { num_matched: u32, offset: usize },
 }
 
 impl Index {
     pub(crate) fn from_dict(dict: &Dictionary, config: &config::Configuration) -> Self {
-        let sa = SuffixArray::new(dict);
+        let wide =
+            config.index_width == config::IndexWidth::Wide || dict.len() > MAX_COMPACT_DICT_LEN;
+        let sa = if wide {
+            SuffixArray::new_wide(dict)
+        } else {
+            SuffixArray::new(dict)
+        };
         Self {
             sa,
             config: config.clone(),
         }
     }
 
+    /// Index from `width` wide suffix array positions stored in native endianness in `positions`
+    pub(crate) fn from_mapped(
+        positions: bytes::Bytes,
+        width: config::IndexWidth,
+        buckets: Vec<SuffixArrayRangeInclusive>,
+        config: &config::Configuration,
+    ) -> Option<Self> {
+        let positions = suffix_array::Positions::from_bytes(positions, width)?;
+        Some(Self {
+            sa: SuffixArray::from_parts(positions, buckets),
+            config: config.clone(),
+        })
+    }
+
+    /// Number of suffix array entries
+    pub(crate) fn len(&self) -> usize {
+        self.sa.positions().len()
+    }
+
+    pub(crate) fn width(&self) -> config::IndexWidth {
+        self.sa.positions().width()
+    }
+
+    /// Suffix array positions in native endianness
+    pub(crate) fn position_bytes(&self) -> &[u8] {
+        self.sa.positions().as_bytes()
+    }
+
+    pub(crate) fn buckets(&self) -> &[SuffixArrayRangeInclusive] {
+        self.sa.buckets()
+    }
+
+    /// Whether this can be the index of `dict`, see [`SuffixArray::is_valid_for`]
+    pub(crate) fn is_valid_for(&self, dict: &Dictionary) -> bool {
+        self.sa.is_valid_for(dict.len())
+    }
+
     pub(crate) fn factorize<'dict>(
         &'_ self,
         dict: &'dict dict::Dictionary,
         mut input: impl Buf,
     ) -> FactorIterator<'dict, '_> {
-        FactorIterator {
-            dict,
-            index: self,
-            remaining_input: input.copy_to_bytes(input.remaining()),
-            config: &self.config,
+        let input = input.copy_to_bytes(input.remaining());
+        let greedy = |lookahead| {
+            FactorIterator::Greedy(GreedyFactorIterator {
+                dict,
+                index: self,
+                remaining_input: input.clone(),
+                config: &self.config,
+                lookahead,
+                next_match: None,
+            })
+        };
+        match self.config.parsing {
+            config::ParsingStrategy::Greedy => greedy(0),
+            config::ParsingStrategy::Lazy => greedy(LAZY_LOOKAHEAD),
+            config::ParsingStrategy::Optimal => {
+                let cost_model = coder::CostModel::new(&self.config, dict.len());
+                let factors = optimal::factorize(self, dict, &input, &cost_model);
+                FactorIterator::Planned(factors.into_iter())
+            }
         }
     }
 
@@ -52,6 +118,7 @@ impl Index {
     }
 
     #[tracing::instrument(skip_all)]
+    #[allow(clippy::cast_possible_truncation)]
     fn find_longest_match(&self, dict: &dict::Dictionary, pattern: &[u8]) -> IndexSearchResult {
         let (mut bounds, mut num_matched) = match self.sa.start_range_from_pattern(pattern) {
             SuffixArrayMatch::NoMatch => return IndexSearchResult::NoMatch,
@@ -76,10 +143,18 @@ impl Index {
                 panic!("this should never happen at this point because we have at least one match")
             }
             SuffixArrayRangeInclusive::Range { start, end: _ } => {
+                let text_pos = self.sa.position(start);
+                // positions of stored suffix arrays are only checked when they are used, a
+                // corrupt position may point past the dictionary or at different bytes
+                let matched = text_pos
+                    .checked_add(num_matched)
+                    .and_then(|match_end| dict.get(text_pos..match_end));
+                if matched != Some(&pattern[..num_matched]) {
+                    return IndexSearchResult::NoMatch;
+                }
                 // we match! take it as far as possible
-                let text_pos = self.sa[start as usize];
                 while let Some(next_sym) = pattern.get(num_matched) {
-                    if let Some(text_sym) = dict.get(text_pos as usize + num_matched) {
+                    if let Some(text_sym) = dict.get(text_pos + num_matched) {
                         if next_sym == text_sym {
                             num_matched += 1;
                         } else {
@@ -99,33 +174,97 @@ impl Index {
     }
 }
 
-pub(crate) struct FactorIterator<'dict, 'encoder> {
+pub(crate) enum FactorIterator<'dict, 'encoder> {
+    /// factors are found one at a time while iterating, optionally looking ahead a few positions
+    Greedy(GreedyFactorIterator<'dict, 'encoder>),
+    /// all factors were determined upfront
+    Planned(std::vec::IntoIter<FactorType>),
+}
+
+impl Iterator for FactorIterator<'_, '_> {
+    type Item = FactorType;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            FactorIterator::Greedy(factors) => factors.next(),
+            FactorIterator::Planned(factors) => factors.next(),
+        }
+    }
+}
+
+pub(crate) struct GreedyFactorIterator<'dict, 'encoder> {
     dict: &'dict dict::Dictionary,
     index: &'encoder Index,
     remaining_input: bytes::Bytes,
     config: &'encoder config::Configuration,
+    lookahead: usize,
+    /// longest match at the start of `remaining_input` if it was already computed during lookahead
+    next_match: Option<IndexSearchResult>,
 }
 
-impl<'dict, 'encoder> Iterator for FactorIterator<'dict, 'encoder> {
+impl GreedyFactorIterator<'_, '_> {
+    /// If a match starting up to `lookahead` positions later reaches further than `current`,
+    /// the number of bytes to emit as a literal instead of copying `current`
+    fn defer_copy(&mut self, current: IndexSearchResult) -> Option<usize> {
+        let IndexSearchResult::Match { num_matched, .. } = current else {
+            return None;
+        };
+        let max_literal = self.config.literal_threshold as usize;
+        if num_matched <= self.config.literal_threshold || max_literal == 0 {
+            return None;
+        }
+        let max_skip = self.lookahead.min(self.remaining_input.len() - 1);
+        for skip in 1..=max_skip {
+            let later = self
+                .index
+                .find_longest_match(self.dict, &self.remaining_input[skip..]);
+            if let IndexSearchResult::Match {
+                num_matched: later_matched,
+                ..
+            } = later
+            {
+                if later_matched as usize > num_matched as usize + skip {
+                    let literal_len = skip.min(max_literal);
+                    if literal_len == skip {
+                        self.next_match = Some(later);
+                    }
+                    return Some(literal_len);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Iterator for GreedyFactorIterator<'_, '_> {
     type Item = FactorType;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.remaining_input.is_empty() {
             return None;
         }
-        let longest_match = self
-            .index
-            .find_longest_match(self.dict, &self.remaining_input);
-        let found_factor = match longest_match {
-            IndexSearchResult::NoMatch => FactorType::Literal(self.remaining_input.slice(0..1)),
-            IndexSearchResult::Match {
-                num_matched,
-                offset,
-            } => {
+        let longest_match = self.next_match.take().unwrap_or_else(|| {
+            self.index
+                .find_longest_match(self.dict, &self.remaining_input)
+        });
+        let deferred = self.defer_copy(longest_match);
+        let found_factor = match (deferred, longest_match) {
+            (Some(literal_len), _) => {
+                FactorType::Literal(self.remaining_input.slice(0..literal_len))
+            }
+            (None, IndexSearchResult::NoMatch) => {
+                FactorType::Literal(self.remaining_input.slice(0..1))
+            }
+            (
+                None,
+                IndexSearchResult::Match {
+                    num_matched,
+                    offset,
+                },
+            ) => {
                 if num_matched <= self.config.literal_threshold {
                     FactorType::Literal(self.remaining_input.slice(0..num_matched as usize))
                 } else {
-                    let num_matched = num_matched as u32;
                     FactorType::Copy {
                         offset,
                         len: num_matched,
@@ -144,6 +283,7 @@ impl<'dict, 'encoder> Iterator for FactorIterator<'dict, 'encoder> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn banana_factorize() {
@@ -163,15 +303,102 @@ mod tests {
         let second = factors.next();
         assert_eq!(
             second,
-            Some(FactorType::Literal(bytes::Bytes::from_static(&[b'c'])))
+            Some(FactorType::Literal(bytes::Bytes::from_static(b"c")))
         );
         let third = factors.next();
         assert_eq!(
             third,
-            Some(FactorType::Literal(bytes::Bytes::from_static(&[b'$'])))
+            Some(FactorType::Literal(bytes::Bytes::from_static(b"$")))
         );
         let forth = factors.next();
         assert_eq!(forth, Some(FactorType::Copy { offset: 1, len: 5 }));
         assert_eq!(factors.next(), None);
     }
+
+    fn reconstruct(factors: impl Iterator<Item = FactorType>, dict: &Dictionary) -> Vec<u8> {
+        factors
+            .flat_map(|factor| match factor {
+                FactorType::Literal(literal) => literal.to_vec(),
+                FactorType::Copy { offset, len } => dict[offset..offset + len as usize].to_vec(),
+            })
+            .collect()
+    }
+
+    fn estimated_bits(
+        factors: impl Iterator<Item = FactorType>,
+        cost_model: &coder::CostModel,
+    ) -> u64 {
+        factors
+            .map(|factor| match factor {
+                FactorType::Literal(literal) => cost_model.literal(literal.len()),
+                FactorType::Copy { offset: _, len } => cost_model.copy(len),
+            })
+            .sum()
+    }
+
+    proptest! {
+        #[test]
+        fn optimal_not_worse_than_greedy(dict: Vec<u8>, text: Vec<u8>, literal_threshold in 1..8u32) {
+            let dict = Dictionary::from(&dict[..]);
+            let greedy_config = crate::Configuration {
+                literal_threshold,
+                ..Default::default()
+            };
+            let optimal_config = crate::Configuration {
+                parsing: config::ParsingStrategy::Optimal,
+                ..greedy_config.clone()
+            };
+            let cost_model = coder::CostModel::new(&greedy_config, dict.len());
+
+            let greedy = Index::from_dict(&dict, &greedy_config);
+            let optimal = Index::from_dict(&dict, &optimal_config);
+
+            let greedy_bits = estimated_bits(greedy.factorize(&dict, &text[..]), &cost_model);
+            let optimal_bits = estimated_bits(optimal.factorize(&dict, &text[..]), &cost_model);
+            assert!(optimal_bits <= greedy_bits);
+
+            assert_eq!(reconstruct(optimal.factorize(&dict, &text[..]), &dict), text);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn lazy_factorize(dict: Vec<u8>, text: Vec<u8>, literal_threshold in 0..8u32) {
+            let dict = Dictionary::from(&dict[..]);
+            let config = crate::Configuration {
+                literal_threshold,
+                parsing: config::ParsingStrategy::Lazy,
+                ..Default::default()
+            };
+            let index = Index::from_dict(&dict, &config);
+            for factor in index.factorize(&dict, &text[..]) {
+                if let FactorType::Literal(literal) = factor {
+                    assert!(literal.len() <= literal_threshold.max(1) as usize);
+                }
+            }
+            assert_eq!(reconstruct(index.factorize(&dict, &text[..]), &dict), text);
+        }
+    }
+
+    #[test]
+    fn lazy_prefers_longer_later_match() {
+        let dict = Dictionary::from(&b"xabcd___abcdefghij"[..]);
+        let config = crate::Configuration {
+            literal_threshold: 1,
+            parsing: config::ParsingStrategy::Lazy,
+            ..Default::default()
+        };
+        let index = Index::from_dict(&dict, &config);
+        // greedy would copy "xabcd" and then "efghij"
+        let mut factors = index.factorize(&dict, &b"xabcdefghij"[..]);
+        assert_eq!(
+            factors.next(),
+            Some(FactorType::Literal(bytes::Bytes::from_static(b"x")))
+        );
+        assert_eq!(
+            factors.next(),
+            Some(FactorType::Copy { offset: 8, len: 10 })
+        );
+        assert_eq!(factors.next(), None);
+    }
 }
@@ -0,0 +1,82 @@
+use bytes::Bytes;
+
+use super::{Index, IndexSearchResult};
+use crate::coder::CostModel;
+use crate::dict;
+use crate::factor::FactorType;
+
+/// Besides the longest match, only copies of up to this many bytes past the literal
+/// threshold are considered at each position. Bounds the work for very long matches.
+const MAX_COPY_CANDIDATES: u32 = 64;
+
+#[derive(Copy, Clone)]
+enum Edge {
+    Literal { len: u32 },
+    Copy { offset: usize, len: u32 },
+}
+
+/// Factorize `input` along the shortest path through the graph whose nodes are the
+/// positions in `input` and whose edges are all literal and copy factors weighted by
+/// their estimated encoded size.
+#[tracing::instrument(skip_all)]
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn factorize(
+    index: &Index,
+    dict: &dict::Dictionary,
+    input: &Bytes,
+    cost_model: &CostModel,
+) -> Vec<FactorType> {
+    let threshold = index.config.literal_threshold;
+    let max_literal = threshold.max(1) as usize;
+
+    let mut cost = vec![u64::MAX; input.len() + 1];
+    let mut edges = vec![Edge::Literal { len: 0 }; input.len() + 1];
+    cost[0] = 0;
+
+    let mut relax = |cost: &mut [u64], from: usize, len: usize, bits: u64, edge: Edge| {
+        let new_cost = cost[from] + bits;
+        if new_cost < cost[from + len] {
+            cost[from + len] = new_cost;
+            edges[from + len] = edge;
+        }
+    };
+
+    for pos in 0..input.len() {
+        let remaining = input.len() - pos;
+        for len in 1..=max_literal.min(remaining) {
+            let edge = Edge::Literal { len: len as u32 };
+            relax(&mut cost, pos, len, cost_model.literal(len), edge);
+        }
+        if let IndexSearchResult::Match {
+            num_matched,
+            offset,
+        } = index.find_longest_match(dict, &input[pos..])
+        {
+            let shorter = threshold + 1..=num_matched.min(threshold + MAX_COPY_CANDIDATES);
+            for len in shorter.chain(std::iter::once(num_matched)) {
+                if len > threshold {
+                    let edge = Edge::Copy { offset, len };
+                    relax(&mut cost, pos, len as usize, cost_model.copy(len), edge);
+                }
+            }
+        }
+    }
+
+    let mut factors = Vec::new();
+    let mut pos = input.len();
+    while pos > 0 {
+        match edges[pos] {
+            Edge::Literal { len } => {
+                let start = pos - len as usize;
+                factors.push(FactorType::Literal(input.slice(start..pos)));
+                pos = start;
+            }
+            Edge::Copy { offset, len } => {
+                factors.push(FactorType::Copy { offset, len });
+                pos -= len as usize;
+            }
+        }
+    }
+    factors.reverse();
+    factors
+}
@@ -1,13 +1,19 @@
 // mostly taken from the suffix_array crate but with modifications
 
-use serde::{Deserialize, Serialize};
-use std::{ops::Deref, slice::from_raw_parts_mut};
+use bytes::Bytes;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::slice::from_raw_parts_mut;
 
 use cdivsufsort::sort_in_place as divsufsort;
+
+use crate::config::IndexWidth;
+
+mod sais;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) enum SuffixArrayRangeInclusive {
     Empty,
-    Range { start: u32, end: u32 },
+    Range { start: usize, end: usize },
 }
 
 impl SuffixArrayRangeInclusive {
@@ -19,7 +25,7 @@ impl SuffixArrayRangeInclusive {
     }
 
     #[cfg(test)]
-    pub(crate) fn len(&self) -> u32 {
+    pub(crate) fn len(&self) -> usize {
         match self {
             SuffixArrayRangeInclusive::Empty => 0,
             SuffixArrayRangeInclusive::Range { start, end } => *end - *start + 1,
@@ -32,36 +38,41 @@ macro_rules! sa_range {
         SuffixArrayRangeInclusive::Range{start: $s, end:$e}
     };
     [$s:tt..$e:tt] => {
-        SuffixArrayRangeInclusive::Range{start: $s, end: $e as u32 -1}
+        SuffixArrayRangeInclusive::Range{start: $s, end: $e - 1}
     };
 }
 
-impl std::ops::Index<SuffixArrayRangeInclusive> for Vec<u32> {
-    type Output = [u32];
+impl<T> std::ops::Index<SuffixArrayRangeInclusive> for [T] {
+    type Output = [T];
 
     fn index(&self, range: SuffixArrayRangeInclusive) -> &Self::Output {
         match range {
             SuffixArrayRangeInclusive::Empty => &[],
-            SuffixArrayRangeInclusive::Range { start, end } => &self[start as usize..=end as usize],
+            SuffixArrayRangeInclusive::Range { start, end } => &self[start..=end],
         }
     }
 }
 
-impl std::ops::Index<&SuffixArrayRangeInclusive> for Vec<u32> {
-    type Output = [u32];
+/// A suffix array entry, either 32 or 64 bits wide
+trait Position: bytemuck::Pod + Ord {
+    fn to_usize(self) -> usize;
+}
 
-    fn index(&self, range: &SuffixArrayRangeInclusive) -> &Self::Output {
-        match range {
-            SuffixArrayRangeInclusive::Empty => &[],
-            SuffixArrayRangeInclusive::Range { start, end } => {
-                &self[*start as usize..=*end as usize]
-            }
-        }
+impl Position for u32 {
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+}
+
+impl Position for u64 {
+    #[allow(clippy::cast_possible_truncation)]
+    fn to_usize(self) -> usize {
+        self as usize
     }
 }
 
-/// Maximum length of the input string.
-pub const MAX_LENGTH: usize = std::i32::MAX as usize;
+/// Maximum length of the input string for the compact (32-bit) suffix array.
+pub const MAX_LENGTH: usize = i32::MAX as usize;
 
 /// Wrapper of the underlying suffix array construction algorithm.
 #[tracing::instrument]
@@ -78,19 +89,133 @@ fn as_signed_integer_slice(sa: &mut [u32]) -> &mut [i32] {
     }
 }
 
+/// Suffix array construction without a length limit in linear time, see [`sais`].
+/// Slower than `saca` on small texts, so only used for the wide index.
+#[tracing::instrument(skip_all)]
+pub fn saca_wide(text: &[u8]) -> Vec<u64> {
+    let mut sa = vec![0u64; text.len()];
+    sais::sais(text, u8::MAX as usize + 1, &mut sa);
+    sa
+}
+
+/// Suffix array positions, either owned or borrowed from a memory mapped file
+#[derive(Clone)]
+pub(crate) enum Positions {
+    Compact(Vec<u32>),
+    Wide(Vec<u64>),
+    Mapped { bytes: Bytes, width: IndexWidth },
+}
+
+/// Borrowed suffix array positions of either width
+#[derive(Serialize)]
+enum PositionSlice<'a> {
+    Compact(&'a [u32]),
+    Wide(&'a [u64]),
+}
+
+#[derive(Deserialize)]
+enum OwnedPositions {
+    Compact(Vec<u32>),
+    Wide(Vec<u64>),
+}
+
+fn copy_unaligned<T: bytemuck::Pod>(bytes: &[u8]) -> Vec<T> {
+    bytes
+        .chunks_exact(std::mem::size_of::<T>())
+        .map(bytemuck::pod_read_unaligned)
+        .collect()
+}
+
+impl Positions {
+    /// Borrow the `width` wide positions stored in native endianness in `bytes`.
+    /// Falls back to copying if `bytes` is not aligned.
+    pub(crate) fn from_bytes(bytes: Bytes, width: IndexWidth) -> Option<Positions> {
+        let entry_bytes = width.position_bytes();
+        if !bytes.len().is_multiple_of(entry_bytes) {
+            return None;
+        }
+        if bytes.as_ptr().align_offset(entry_bytes) == 0 {
+            return Some(Positions::Mapped { bytes, width });
+        }
+        Some(match width {
+            IndexWidth::Compact => Positions::Compact(copy_unaligned(&bytes)),
+            IndexWidth::Wide => Positions::Wide(copy_unaligned(&bytes)),
+        })
+    }
+
+    fn as_slice(&self) -> PositionSlice<'_> {
+        match self {
+            Positions::Compact(positions) => PositionSlice::Compact(positions),
+            Positions::Wide(positions) => PositionSlice::Wide(positions),
+            Positions::Mapped {
+                bytes,
+                width: IndexWidth::Compact,
+            } => PositionSlice::Compact(bytemuck::cast_slice(bytes)),
+            Positions::Mapped {
+                bytes,
+                width: IndexWidth::Wide,
+            } => PositionSlice::Wide(bytemuck::cast_slice(bytes)),
+        }
+    }
+
+    pub(crate) fn width(&self) -> IndexWidth {
+        match self.as_slice() {
+            PositionSlice::Compact(_) => IndexWidth::Compact,
+            PositionSlice::Wide(_) => IndexWidth::Wide,
+        }
+    }
+
+    /// The positions in native endianness
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        match self.as_slice() {
+            PositionSlice::Compact(positions) => bytemuck::cast_slice(positions),
+            PositionSlice::Wide(positions) => bytemuck::cast_slice(positions),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match self.as_slice() {
+            PositionSlice::Compact(positions) => positions.len(),
+            PositionSlice::Wide(positions) => positions.len(),
+        }
+    }
+
+    fn get(&self, sa_index: usize) -> usize {
+        match self.as_slice() {
+            PositionSlice::Compact(positions) => positions[sa_index].to_usize(),
+            PositionSlice::Wide(positions) => positions[sa_index].to_usize(),
+        }
+    }
+}
+
+impl Serialize for Positions {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_slice().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Positions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match OwnedPositions::deserialize(deserializer)? {
+            OwnedPositions::Compact(positions) => Positions::Compact(positions),
+            OwnedPositions::Wide(positions) => Positions::Wide(positions),
+        })
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct SuffixArray {
-    sa: Vec<u32>,
+    sa: Positions,
     bkt: Vec<SuffixArrayRangeInclusive>,
 }
 
-#[tracing::instrument]
+#[tracing::instrument(skip_all)]
 fn compute_buckets(text: &[u8]) -> Vec<SuffixArrayRangeInclusive> {
     let num_uniq_chars: usize = u8::MAX as usize + 1;
     let num_zero_grams = 1;
     let num_bigrams = num_uniq_chars * num_uniq_chars;
     let num_buckets: usize = num_bigrams + num_uniq_chars + num_zero_grams;
-    let mut bkt_cnts = vec![0; num_buckets];
+    let mut bkt_cnts = vec![0usize; num_buckets];
     let mut bkt = vec![SuffixArrayRangeInclusive::Empty; num_buckets];
 
     if text.is_empty() {
@@ -98,7 +223,7 @@ fn compute_buckets(text: &[u8]) -> Vec<SuffixArrayRangeInclusive> {
     }
 
     // full range
-    let tlen = text.len() as u32;
+    let tlen = text.len();
     bkt[0] = sa_range![0..tlen];
 
     // count occurrences.
@@ -120,7 +245,7 @@ fn compute_buckets(text: &[u8]) -> Vec<SuffixArrayRangeInclusive> {
         let uidx = uidx + num_zero_grams;
         if bkt_cnts[uidx] != 0 {
             let start = sum;
-            let end = sum + bkt_cnts[uidx] as u32;
+            let end = sum + bkt_cnts[uidx];
             bkt[uidx] = sa_range![start..end];
             sum += bkt_cnts[uidx];
             bkt_cnts[uidx] = start;
@@ -135,11 +260,11 @@ fn compute_buckets(text: &[u8]) -> Vec<SuffixArrayRangeInclusive> {
             sum += 1;
         }
         for second in 0..num_uniq_chars {
-            let bigram_idx = first * num_uniq_chars + second as usize;
+            let bigram_idx = first * num_uniq_chars + second;
             let bigram_idx = bigram_idx + num_uniq_chars + num_zero_grams;
             if bkt_cnts[bigram_idx] != 0 {
                 let start = sum;
-                let end = sum + bkt_cnts[bigram_idx] as u32;
+                let end = sum + bkt_cnts[bigram_idx];
                 bkt[bigram_idx] = sa_range![start..end];
                 sum += bkt_cnts[bigram_idx];
             }
@@ -205,10 +330,49 @@ impl SuffixArray {
         saca(text, &mut sa[..]);
         SuffixArray {
             bkt: compute_buckets(text),
-            sa,
+            sa: Positions::Compact(sa),
         }
     }
 
+    /// Suffix array with 64-bit positions for texts longer than `MAX_LENGTH`
+    #[tracing::instrument(skip_all)]
+    pub fn new_wide(text: &[u8]) -> Self {
+        SuffixArray {
+            bkt: compute_buckets(text),
+            sa: Positions::Wide(saca_wide(text)),
+        }
+    }
+
+    pub(crate) fn from_parts(sa: Positions, bkt: Vec<SuffixArrayRangeInclusive>) -> Self {
+        SuffixArray { sa, bkt }
+    }
+
+    pub(crate) fn positions(&self) -> &Positions {
+        &self.sa
+    }
+
+    /// Text position of the suffix at `sa_index`. Not checked against the text, a corrupt
+    /// stored suffix array can contain positions past its end.
+    #[inline]
+    pub(crate) fn position(&self, sa_index: usize) -> usize {
+        self.sa.get(sa_index)
+    }
+
+    pub(crate) fn buckets(&self) -> &[SuffixArrayRangeInclusive] {
+        &self.bkt
+    }
+
+    /// Whether this can be the suffix array of a text of `text_len` bytes: one position per
+    /// suffix and all buckets within the suffix array. Does not read the positions, so it is
+    /// cheap for memory mapped suffix arrays; positions are bounds checked when they are used.
+    pub(crate) fn is_valid_for(&self, text_len: usize) -> bool {
+        let buckets_in_bounds = self.bkt.iter().all(|bucket| match bucket {
+            SuffixArrayRangeInclusive::Empty => true,
+            SuffixArrayRangeInclusive::Range { start, end } => start <= end && *end < text_len,
+        });
+        self.sa.len() == text_len && buckets_in_bounds
+    }
+
     pub(crate) fn start_range_from_pattern(&self, pat: &[u8]) -> SuffixArrayMatch {
         get_bucket(&self.bkt, pat)
     }
@@ -218,6 +382,16 @@ impl SuffixArray {
         self.bkt[0]
     }
 
+    #[cfg(any(test, feature = "fuzzing"))]
+    pub(crate) fn positions_in(&self, range: SuffixArrayRangeInclusive) -> Vec<usize> {
+        match range {
+            SuffixArrayRangeInclusive::Empty => Vec::new(),
+            SuffixArrayRangeInclusive::Range { start, end } => (start..=end)
+                .map(|sa_index| self.position(sa_index))
+                .collect(),
+        }
+    }
+
     #[inline]
     pub(crate) fn refine_bounds(
         &self,
@@ -226,56 +400,55 @@ impl SuffixArray {
         offset: usize,
         text: &[u8],
     ) -> SuffixArrayRangeInclusive {
-        let (mut new_left, mut new_right) = match init_range {
-            SuffixArrayRangeInclusive::Empty => return init_range,
-            SuffixArrayRangeInclusive::Range { start, end } => (start, start),
-        };
-        let sa_range = &self.sa[&init_range];
-
-        // refine left bound
-        new_left += sa_range.partition_point(|&probe| {
-            // we might be going past the end of the text with the probe + offset
-            if let Some(text_sym) = text.get(probe as usize + offset) {
-                return *text_sym < pat_sym;
-            }
-            true
-        }) as u32;
-
-        // if let Some(probe) = self.sa.get(new_left as usize) {
-        //     if let Some(text_sym) = text.get(*probe as usize + offset) {
-        //         if *text_sym != pat_sym {
-        //             return SuffixArrayRangeInclusive::Empty;
-        //         }
-        //     }
-        // }
-
-        // refine right bound
-        new_right += sa_range.partition_point(|&probe| {
-            // we might be going past the end of the text with the probe + offset
-            if let Some(text_sym) = text.get(probe as usize + offset) {
-                return *text_sym <= pat_sym;
-            }
-            true
-        }) as u32;
-
-        // there must be a match if those two are different
-        if new_left < new_right {
-            sa_range![new_left..new_right]
-        } else {
-            SuffixArrayRangeInclusive::Empty
+        match self.sa.as_slice() {
+            PositionSlice::Compact(sa) => refine_bounds(sa, init_range, pat_sym, offset, text),
+            PositionSlice::Wide(sa) => refine_bounds(sa, init_range, pat_sym, offset, text),
         }
     }
 }
 
-impl Deref for SuffixArray {
-    type Target = Vec<u32>;
+#[inline]
+fn refine_bounds<P: Position>(
+    sa: &[P],
+    init_range: SuffixArrayRangeInclusive,
+    pat_sym: u8,
+    offset: usize,
+    text: &[u8],
+) -> SuffixArrayRangeInclusive {
+    let (mut new_left, mut new_right) = match init_range {
+        SuffixArrayRangeInclusive::Empty => return init_range,
+        SuffixArrayRangeInclusive::Range { start, end: _ } => (start, start),
+    };
+    let sa_range = &sa[init_range];
 
-    fn deref(&self) -> &Self::Target {
-        &self.sa
+    // refine left bound
+    new_left += sa_range.partition_point(|&probe| {
+        // we might be going past the end of the text with the probe + offset
+        if let Some(text_sym) = text.get(probe.to_usize().saturating_add(offset)) {
+            return *text_sym < pat_sym;
+        }
+        true
+    });
+
+    // refine right bound
+    new_right += sa_range.partition_point(|&probe| {
+        // we might be going past the end of the text with the probe + offset
+        if let Some(text_sym) = text.get(probe.to_usize().saturating_add(offset)) {
+            return *text_sym <= pat_sym;
+        }
+        true
+    });
+
+    // there must be a match if those two are different
+    if new_left < new_right {
+        sa_range![new_left..new_right]
+    } else {
+        SuffixArrayRangeInclusive::Empty
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::cast_possible_truncation)]
 mod tests {
     use super::*;
     use proptest::prelude::*;
@@ -304,42 +477,42 @@ mod tests {
             get_bucket(&buckets, "$".as_bytes()),
             SuffixArrayMatch::Match {
                 num_matched: 1,
-                range: sa_range![0u32..1u32]
+                range: sa_range![0..1]
             }
         );
         assert_eq!(
             get_bucket(&buckets, "a".as_bytes()),
             SuffixArrayMatch::Match {
                 num_matched: 1,
-                range: sa_range![1u32..4u32]
+                range: sa_range![1..4]
             }
         );
         assert_eq!(
             get_bucket(&buckets, "an".as_bytes()),
             SuffixArrayMatch::Match {
                 num_matched: 2,
-                range: sa_range![2u32..4u32]
+                range: sa_range![2..4]
             }
         );
         assert_eq!(
             get_bucket(&buckets, "".as_bytes()),
             SuffixArrayMatch::Match {
                 num_matched: 0,
-                range: sa_range![0u32..7u32]
+                range: sa_range![0..7]
             }
         );
         assert_eq!(
             get_bucket(&buckets, "n".as_bytes()),
             SuffixArrayMatch::Match {
                 num_matched: 1,
-                range: sa_range![5u32..7u32]
+                range: sa_range![5..7]
             }
         );
         assert_eq!(
             get_bucket(&buckets, "na".as_bytes()),
             SuffixArrayMatch::Match {
                 num_matched: 2,
-                range: sa_range![5u32..7u32]
+                range: sa_range![5..7]
             }
         );
     }
@@ -374,7 +547,7 @@ mod tests {
         #[test]
         fn random_refine(text: String) {
             let mut byte_counts = vec![0usize;u8::MAX as usize+1];
-            for b in text.as_bytes().iter() {
+            for b in text.as_bytes() {
                 byte_counts[*b as usize] += 1;
             }
             let sa = SuffixArray::new(text.as_bytes());
@@ -404,7 +577,7 @@ mod tests {
 
     prop_compose! {
         fn text_and_index()(text in ".+")
-                           (index in 0..text.as_bytes().len(),text in Just(text))
+                           (index in 0..text.len(),text in Just(text))
                         -> (String, usize) {
            (text, index)
        }
@@ -421,7 +594,7 @@ mod tests {
                 let refined_range = sa.refine_bounds(start_range, *chr, offset, text.as_bytes());
                 let mut found = false;
                 for text_window in text.as_bytes().windows(offset+1) {
-                    if text_window == &pattern.as_bytes()[..offset+1] {
+                    if text_window == &pattern.as_bytes()[..=offset] {
                         found = true;
                         break;
                     }
@@ -439,15 +612,13 @@ mod tests {
 
         #[test]
         fn random_refine_multi((text, index) in text_and_index()) {
-            let u32_idx = index as u32;
             let sa = SuffixArray::new(text.as_bytes());
             let mut start_range = sa.start_range();
             for (offset, chr) in text.as_bytes()[index..].iter().enumerate() {
                 let refined_range = sa.refine_bounds(start_range, *chr, offset, text.as_bytes());
                 assert_ne!(refined_range,SuffixArrayRangeInclusive::Empty);
                 start_range = refined_range;
-                let sa_range = &sa[start_range];
-                assert!(sa_range.contains(&u32_idx));
+                assert!(sa.positions_in(start_range).contains(&index));
             }
         }
     }
@@ -476,7 +647,7 @@ mod tests {
                 let refined_range = sa.refine_bounds(start_range, *chr, offset, text.as_bytes());
                 let mut found = false;
                 for text_window in text.as_bytes().windows(offset+1) {
-                    if text_window == &pattern[..offset+1] {
+                    if text_window == &pattern[..=offset] {
                         found = true;
                         break;
                     }
@@ -496,7 +667,6 @@ mod tests {
     proptest! {
         #[test]
         fn random_refine_multi_with_buckets((text, index) in text_and_index()) {
-            let u32_idx = index as u32;
             let sa = SuffixArray::new(text.as_bytes());
             let pattern = &text.as_bytes()[index..];
             let (mut start_range, mut offset) = match sa.start_range_from_pattern(pattern) {
@@ -511,8 +681,7 @@ mod tests {
                 dbg!(&refined_range);
                 assert_ne!(refined_range,SuffixArrayRangeInclusive::Empty);
                 start_range = refined_range;
-                let sa_range = &sa[start_range];
-                assert!(sa_range.contains(&u32_idx));
+                assert!(sa.positions_in(start_range).contains(&index));
                 offset += 1;
             }
         }
@@ -522,7 +691,7 @@ mod tests {
         #[test]
         fn random_unigram_buckets(text: String) {
             let mut byte_counts = vec![0usize;u8::MAX as usize+1];
-            for b in text.as_bytes().iter() {
+            for b in text.as_bytes() {
                 byte_counts[*b as usize] += 1;
             }
             let buckets = compute_buckets(text.as_bytes());
@@ -531,10 +700,54 @@ mod tests {
                 let res = get_bucket(&buckets,data.as_slice());
                 match res {
                     SuffixArrayMatch::NoMatch => assert_eq!(cnt,0),
-                    SuffixArrayMatch::Match { num_matched: 1, range} => assert_eq!(range.len() as usize,cnt),
-                    _ => panic!()
+                    SuffixArrayMatch::Match { num_matched: 1, range} => assert_eq!(range.len(),cnt),
+                    SuffixArrayMatch::Match { .. } => panic!()
                 }
             }
         }
     }
+
+    proptest! {
+        #[test]
+        fn wide_saca_matches_compact(text: Vec<u8>) {
+            let mut sa = vec![0u32; text.len()];
+            saca(&text, &mut sa);
+            let wide: Vec<u64> = sa.into_iter().map(u64::from).collect();
+            assert_eq!(saca_wide(&text), wide);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn wide_saca_matches_compact_small_alphabet(text in prop::collection::vec(0..3u8, 0..2048)) {
+            let mut sa = vec![0u32; text.len()];
+            saca(&text, &mut sa);
+            let wide: Vec<u64> = sa.into_iter().map(u64::from).collect();
+            assert_eq!(saca_wide(&text), wide);
+        }
+    }
+
+    #[test]
+    fn wide_saca_repetitive_text() {
+        let text = b"ab".repeat(1 << 20);
+        let wide = saca_wide(&text);
+
+        let mut sa = vec![0u32; text.len()];
+        saca(&text, &mut sa);
+        assert!(wide
+            .iter()
+            .zip(&sa)
+            .all(|(&wide, &sa)| wide == u64::from(sa)));
+    }
+
+    #[test]
+    fn banana_refine_wide() {
+        let text = "banana$";
+        let sa = SuffixArray::new_wide(text.as_bytes());
+
+        let refined_range = sa.refine_bounds(sa.start_range(), b'a', 0, text.as_bytes());
+        assert_eq!(refined_range, sa_range![1..=3]);
+        let refined_range = sa.refine_bounds(refined_range, b'n', 1, text.as_bytes());
+        assert_eq!(sa.positions_in(refined_range), vec![3, 1]);
+    }
 }
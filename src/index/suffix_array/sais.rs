@@ -0,0 +1,219 @@
+//! Linear time suffix array construction by induced sorting (SA-IS, Nong, Zhang and Chan 2009)
+//! with 64-bit positions. The end of the text acts as a virtual sentinel smaller than every
+//! symbol, so shorter suffixes sort before longer suffixes sharing the same prefix.
+//!
+//! Besides the suffix array and one bit per symbol, only the buckets of the reduced
+//! problem are allocated: the reduced string and its suffix array live in the output.
+
+/// Marks a suffix array slot not filled yet
+const EMPTY: u64 = u64::MAX;
+
+/// A text symbol, bytes for the dictionary and names of LMS substrings for reduced problems
+pub(super) trait Symbol: Copy + Eq {
+    fn rank(self) -> usize;
+}
+
+impl Symbol for u8 {
+    fn rank(self) -> usize {
+        self as usize
+    }
+}
+
+impl Symbol for u64 {
+    #[allow(clippy::cast_possible_truncation)]
+    fn rank(self) -> usize {
+        self as usize
+    }
+}
+
+/// Suffix types, `true` for S-type suffixes which are smaller than their successor
+struct Types(Vec<u64>);
+
+impl Types {
+    fn classify<T: Symbol>(text: &[T]) -> Self {
+        let mut types = Types(vec![0; text.len().div_ceil(64)]);
+        // the last suffix is larger than the virtual sentinel and therefore L-type
+        for i in (0..text.len().saturating_sub(1)).rev() {
+            if text[i] == text[i + 1] {
+                if types.is_s(i + 1) {
+                    types.set_s(i);
+                }
+            } else if text[i].rank() < text[i + 1].rank() {
+                types.set_s(i);
+            }
+        }
+        types
+    }
+
+    fn is_s(&self, i: usize) -> bool {
+        self.0[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    fn set_s(&mut self, i: usize) {
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+
+    /// Leftmost S-type suffix of a run of S-type suffixes
+    fn is_lms(&self, i: usize) -> bool {
+        i > 0 && self.is_s(i) && !self.is_s(i - 1)
+    }
+}
+
+/// Start (`end == false`) or end (`end == true`) of the bucket of each symbol
+fn buckets<T: Symbol>(text: &[T], alphabet: usize, end: bool) -> Vec<u64> {
+    let mut counts = vec![0u64; alphabet];
+    for &sym in text {
+        counts[sym.rank()] += 1;
+    }
+    let mut sum = 0;
+    for count in &mut counts {
+        sum += *count;
+        *count = if end { sum } else { sum - *count };
+    }
+    counts
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn induce_l<T: Symbol>(text: &[T], types: &Types, alphabet: usize, sa: &mut [u64]) {
+    let mut starts = buckets(text, alphabet, false);
+    // the virtual sentinel precedes all suffixes and induces the last suffix
+    let last = text.len() - 1;
+    sa[starts[text[last].rank()] as usize] = last as u64;
+    starts[text[last].rank()] += 1;
+    for i in 0..sa.len() {
+        let pos = sa[i];
+        if pos == EMPTY || pos == 0 {
+            continue;
+        }
+        let prev = pos as usize - 1;
+        if !types.is_s(prev) {
+            let bucket = &mut starts[text[prev].rank()];
+            sa[*bucket as usize] = prev as u64;
+            *bucket += 1;
+        }
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn induce_s<T: Symbol>(text: &[T], types: &Types, alphabet: usize, sa: &mut [u64]) {
+    let mut ends = buckets(text, alphabet, true);
+    for i in (0..sa.len()).rev() {
+        let pos = sa[i];
+        if pos == EMPTY || pos == 0 {
+            continue;
+        }
+        let prev = pos as usize - 1;
+        if types.is_s(prev) {
+            let bucket = &mut ends[text[prev].rank()];
+            *bucket -= 1;
+            sa[*bucket as usize] = prev as u64;
+        }
+    }
+}
+
+/// Whether the LMS substrings starting at `a` and `b` are equal. A substring reaching the
+/// end of the text contains the unique sentinel and equals no other substring.
+fn equal_lms_substrings<T: Symbol>(text: &[T], types: &Types, a: usize, b: usize) -> bool {
+    for d in 0.. {
+        let (x, y) = (a + d, b + d);
+        if x == text.len() || y == text.len() {
+            return false;
+        }
+        if text[x] != text[y] || types.is_s(x) != types.is_s(y) {
+            return false;
+        }
+        if d > 0 && (types.is_lms(x) || types.is_lms(y)) {
+            return types.is_lms(x) && types.is_lms(y);
+        }
+    }
+    unreachable!()
+}
+
+/// Write the suffix array of `text`, whose symbols rank below `alphabet`, into `sa`
+#[allow(clippy::cast_possible_truncation)]
+pub(super) fn sais<T: Symbol>(text: &[T], alphabet: usize, sa: &mut [u64]) {
+    let n = text.len();
+    debug_assert_eq!(sa.len(), n);
+    match n {
+        0 => return,
+        1 => {
+            sa[0] = 0;
+            return;
+        }
+        _ => {}
+    }
+    let types = Types::classify(text);
+
+    // (1) sort the LMS substrings by inducing from the LMS suffixes in text order
+    sa.fill(EMPTY);
+    let mut ends = buckets(text, alphabet, true);
+    for i in (1..n).filter(|&i| types.is_lms(i)) {
+        let bucket = &mut ends[text[i].rank()];
+        *bucket -= 1;
+        sa[*bucket as usize] = i as u64;
+    }
+    induce_l(text, &types, alphabet, sa);
+    induce_s(text, &types, alphabet, sa);
+
+    // (2) name the sorted LMS substrings. LMS suffixes are at least two positions apart,
+    // so the name of the substring at `pos` fits into slot `num_lms + pos / 2`
+    let mut num_lms = 0;
+    for i in 0..n {
+        if types.is_lms(sa[i] as usize) {
+            sa[num_lms] = sa[i];
+            num_lms += 1;
+        }
+    }
+    if num_lms == 0 {
+        // no S-type suffixes, the text is non-increasing and already sorted by inducing
+        return;
+    }
+    sa[num_lms..].fill(EMPTY);
+    let mut num_names = 0;
+    let mut prev: Option<usize> = None;
+    for i in 0..num_lms {
+        let pos = sa[i] as usize;
+        if prev.is_none_or(|prev| !equal_lms_substrings(text, &types, prev, pos)) {
+            num_names += 1;
+            prev = Some(pos);
+        }
+        sa[num_lms + pos / 2] = num_names as u64 - 1;
+    }
+    let mut j = n;
+    for i in (num_lms..n).rev() {
+        if sa[i] != EMPTY {
+            j -= 1;
+            sa[j] = sa[i];
+        }
+    }
+
+    // (3) sort the LMS suffixes by the suffix array of the reduced string of names
+    let (reduced_sa, reduced) = sa.split_at_mut(n - num_lms);
+    let reduced_sa = &mut reduced_sa[..num_lms];
+    if num_names < num_lms {
+        sais(&*reduced, num_names, reduced_sa);
+    } else {
+        for (i, &name) in reduced.iter().enumerate() {
+            reduced_sa[name as usize] = i as u64;
+        }
+    }
+    for (slot, i) in reduced.iter_mut().zip((1..n).filter(|&i| types.is_lms(i))) {
+        *slot = i as u64;
+    }
+    for pos in reduced_sa.iter_mut() {
+        *pos = reduced[*pos as usize];
+    }
+
+    // (4) induce all suffixes from the sorted LMS suffixes
+    sa[num_lms..].fill(EMPTY);
+    let mut ends = buckets(text, alphabet, true);
+    for i in (0..num_lms).rev() {
+        let pos = sa[i];
+        sa[i] = EMPTY;
+        let bucket = &mut ends[text[pos as usize].rank()];
+        *bucket -= 1;
+        sa[*bucket as usize] = pos;
+    }
+    induce_l(text, &types, alphabet, sa);
+    induce_s(text, &types, alphabet, sa);
+}
@@ -36,7 +36,10 @@
 #![warn(clippy::pedantic)]
 #![warn(missing_docs)]
 
-mod coder;
+pub mod archive;
+mod batch;
+/// compression codecs for the literal, offset and length streams
+pub mod coder;
 mod config;
 mod decoder;
 /// dictionary construction related items
@@ -44,18 +47,27 @@ pub mod dict;
 mod encoder;
 mod error;
 mod factor;
+mod frame;
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub mod fuzzing;
 mod index;
+mod mapped;
 mod scratch;
+mod stats;
+pub mod stream;
 mod vbyte;
 
 use bytes::{Buf, BufMut};
 
-pub use config::Configuration;
+pub use batch::BatchPool;
+pub use config::{Configuration, IndexWidth, ParsingStrategy};
 use decoder::Decoder;
 pub use dict::Dictionary;
 use encoder::Encoder;
 
-pub use error::Error;
+pub use error::{ChecksumPart, Error};
+pub use stats::{EncodeStats, LEN_BUCKETS};
 
 /// Main RLZ compressor class
 pub struct RlzCompressor {
@@ -63,6 +75,7 @@ pub struct RlzCompressor {
     config: config::Configuration,
     encoder: Option<Encoder>,
     decoder: Decoder,
+    fingerprint: frame::Fingerprint,
 }
 
 impl RlzCompressor {
@@ -72,16 +85,137 @@ impl RlzCompressor {
         RlzBuilder::default()
     }
 
-    /// Encode a vector of bytes against the dictionary
+    fn from_parts(
+        dict: Dictionary,
+        config: config::Configuration,
+        encoder: Option<Encoder>,
+        decoder: Decoder,
+        fingerprint: frame::Fingerprint,
+    ) -> Self {
+        RlzCompressor {
+            dict,
+            config,
+            encoder,
+            decoder,
+            fingerprint,
+        }
+    }
+
+    /// Encode a vector of bytes against the dictionary. The output starts with a header
+    /// identifying the dictionary and configuration so decoding with a different compressor
+    /// fails instead of producing garbage.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no encoder is available or the factor streams can not be compressed
     #[tracing::instrument(skip_all)]
-    pub fn encode(&self, input: impl Buf, output: impl BufMut) -> Result<usize, Error> {
+    pub fn encode(&self, mut input: impl Buf, mut output: impl BufMut) -> Result<usize, Error> {
         if let Some(encoder) = &self.encoder {
-            encoder.encode(&self.dict, input, output)
+            let input = input.copy_to_bytes(input.remaining());
+            let header_bytes = self.write_header(&input, &mut output);
+            Ok(header_bytes + encoder.encode(&self.dict, input, output)?)
         } else {
             Err(Error::NoEncoderAvailable)
         }
     }
 
+    /// Encode a vector of bytes against the dictionary like [`RlzCompressor::encode`] and
+    /// collect statistics about its factors and streams. Merge the statistics of many
+    /// documents with [`EncodeStats::merge`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if no encoder is available or the factor streams can not be compressed
+    #[tracing::instrument(skip_all)]
+    pub fn encode_with_stats(
+        &self,
+        mut input: impl Buf,
+        mut output: impl BufMut,
+    ) -> Result<(usize, EncodeStats), Error> {
+        let encoder = self.encoder.as_ref().ok_or(Error::NoEncoderAvailable)?;
+        let input = input.copy_to_bytes(input.remaining());
+        let mut frame = Vec::new();
+        let header_bytes = self.write_header(&input, &mut frame);
+        let mut stats = EncodeStats::new(self.dict.len());
+        encoder.encode_with_stats(&self.dict, input, &mut frame, &mut stats)?;
+
+        let mut sizes = self
+            .config
+            .factor_compression
+            .stream_sizes(&frame[header_bytes..])?;
+        sizes.header += header_bytes;
+        stats.finish_document(sizes);
+        output.put_slice(&frame);
+        Ok((frame.len(), stats))
+    }
+
+    /// Write the frame header of the encoded `input` to `output`
+    fn write_header(&self, input: &[u8], output: impl BufMut) -> usize {
+        let checksum = self
+            .config
+            .factor_compression
+            .has_checksums()
+            .then(|| frame::checksum(input));
+        let header = frame::FrameHeader {
+            fingerprint: self.fingerprint,
+            decoded_len: input.len() as u64,
+            checksum,
+        };
+        header.write(output)
+    }
+
+    /// Encode all `documents` on the workers of `pool` and return the encoded documents in
+    /// the same order. Each worker reuses its own scratch space for all documents it encodes.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no encoder is available or the factor streams of any document can not be
+    /// compressed
+    #[tracing::instrument(skip_all)]
+    pub fn encode_batch<D>(
+        &self,
+        documents: impl IntoIterator<Item = D, IntoIter: Send>,
+        pool: &BatchPool,
+    ) -> Result<Vec<Vec<u8>>, Error>
+    where
+        D: AsRef<[u8]> + Send,
+    {
+        let encoder = self.encoder.as_ref().ok_or(Error::NoEncoderAvailable)?;
+        pool.map_ordered(documents.into_iter(), |document, scratch| {
+            let input = bytes::Bytes::copy_from_slice(document.as_ref());
+            let mut output = Vec::new();
+            self.write_header(&input, &mut output);
+            encoder.encode_with(&self.dict, input, &mut output, scratch)?;
+            Ok(output)
+        })
+    }
+
+    /// Decode all `documents` on the workers of `pool` and return the decoded documents in
+    /// the same order. Each worker reuses its own scratch space for all documents it decodes.
+    ///
+    /// # Errors
+    ///
+    /// Fails if any document fails to decode, see [`RlzCompressor::decode`]
+    #[tracing::instrument(skip_all)]
+    pub fn decode_batch<D>(
+        &self,
+        documents: impl IntoIterator<Item = D, IntoIter: Send>,
+        pool: &BatchPool,
+    ) -> Result<Vec<Vec<u8>>, Error>
+    where
+        D: AsRef<[u8]> + Send,
+    {
+        pool.map_ordered(documents.into_iter(), |document, scratch| {
+            let input = document.as_ref();
+            // the header is not verified yet, so reserve at most a small multiple of the input
+            let capacity = self.decoded_len(input)?.min(input.len().saturating_mul(4));
+            let mut output = Vec::with_capacity(capacity);
+            self.decoder
+                .decode_with(&self.dict, self.fingerprint, input, &mut output, scratch)?;
+            Ok(output)
+        })
+    }
+
     /// If `RlzCompressor` is loaded from disk we rebuild the index to enable encoding
     pub fn enable_encode(&mut self) {
         if self.encoder.is_none() {
@@ -91,39 +225,307 @@ impl RlzCompressor {
         }
     }
 
-    /// Decode a vector of bytes that was compressed against the dictionary
+    /// Decode a vector of bytes that was compressed against the dictionary, returns the
+    /// number of bytes written
+    ///
+    /// # Errors
+    ///
+    /// Fails if `input` was encoded against a different dictionary or configuration,
+    /// the factor streams can not be decompressed, the decoded length does not match
+    /// the stored length or writing the output fails
     #[tracing::instrument(skip_all)]
     pub fn decode(&self, input: &[u8], output: impl std::io::Write) -> Result<usize, Error> {
-        self.decoder.decode(&self.dict, input, output)
+        self.decoder
+            .decode(&self.dict, self.fingerprint, input, output)
+    }
+
+    /// Decode a document that was compressed against the dictionary straight into `output`,
+    /// returns the number of bytes written. Use [`RlzCompressor::decoded_len`] to size
+    /// `output`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `output` is shorter than the decoded document, `input` was encoded against
+    /// a different dictionary or configuration, the factor streams can not be decompressed
+    /// or the decoded length does not match the stored length
+    #[tracing::instrument(skip_all)]
+    pub fn decode_into(&self, input: &[u8], output: &mut [u8]) -> Result<usize, Error> {
+        self.decoder
+            .decode_into(&self.dict, self.fingerprint, input, output)
+    }
+
+    /// Length of the document in `input` once decoded, read from its frame header without
+    /// decoding the document
+    ///
+    /// # Errors
+    ///
+    /// Fails if `input` does not start with a frame header, was encoded against a different
+    /// dictionary or configuration or its length does not fit into `usize`
+    pub fn decoded_len(&self, mut input: &[u8]) -> Result<usize, Error> {
+        let header = frame::FrameHeader::read(&mut input)?;
+        header.check(self.fingerprint)?;
+        usize::try_from(header.decoded_len).map_err(|_| Error::InvalidFrame)
+    }
+
+    /// Decode only bytes `range` of a document that was compressed against the dictionary,
+    /// copying just the factors covering the range. Returns the number of bytes written.
+    /// The document checksum is not verified as the rest of the document is never decoded.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `range` is not part of the document, `input` was encoded against a different
+    /// dictionary or configuration, the factor streams can not be decompressed or writing
+    /// the output fails
+    #[tracing::instrument(skip_all)]
+    pub fn decode_range(
+        &self,
+        input: &[u8],
+        range: std::ops::Range<usize>,
+        output: impl std::io::Write,
+    ) -> Result<usize, Error> {
+        self.decoder
+            .decode_range(&self.dict, self.fingerprint, input, range, output)
     }
 
-    /// Store the compressor (the dict + config) on disk
+    /// Encode everything `input` yields as a stream written to `output`, holding at most
+    /// one chunk of input in memory. See [`crate::stream`] for the layout.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading the input, encoding or writing the output fails
+    #[tracing::instrument(skip_all)]
+    pub fn encode_reader<W: std::io::Write>(
+        &self,
+        mut input: impl std::io::Read,
+        output: W,
+    ) -> Result<W, Error> {
+        let mut writer = stream::RlzWriter::new(self, output);
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let len = match input.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error.into()),
+            };
+            writer.push(&buffer[..len])?;
+        }
+        writer.finish()
+    }
+
+    /// Lengths of the factors of an encoded document in order. Factors of at most
+    /// [`Configuration::literal_threshold`] bytes are literals, longer ones are copied from
+    /// the dictionary.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `input` was encoded against a different dictionary or configuration or the
+    /// length stream can not be decompressed
+    pub fn factor_lens(&self, input: &[u8]) -> Result<Vec<u32>, Error> {
+        self.decoder
+            .factor_lens(&self.dict, self.fingerprint, input)
+    }
+
+    /// Configuration used for encoding and decoding
+    #[must_use]
+    pub fn config(&self) -> &Configuration {
+        &self.config
+    }
+
+    /// Dictionary factors are copied from
+    #[must_use]
+    pub fn dictionary(&self) -> &Dictionary {
+        &self.dict
+    }
+
+    /// Sizes of the literal, offset and length streams of an encoded document
+    ///
+    /// # Errors
+    ///
+    /// Fails if `input` is not an encoded document
+    pub fn stream_sizes(&self, mut input: &[u8]) -> Result<coder::StreamSizes, Error> {
+        let header = frame::FrameHeader::read(&mut input)?;
+        let mut sizes = self.config.factor_compression.stream_sizes(input)?;
+        sizes.header += header.len();
+        Ok(sizes)
+    }
+
+    /// Store the compressor (the dict + config) on disk. Checksums of the dictionary and
+    /// the configuration are verified by [`RlzCompressor::load`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if serializing or writing the compressor fails
     #[tracing::instrument(skip_all)]
     pub fn store(&self, output: impl std::io::Write) -> Result<(), Error> {
+        self.store_parts(output, None)
+    }
+
+    /// Store the compressor (dict + config) together with the index used for encoding so that
+    /// [`RlzCompressor::load_with_index`] does not have to rebuild it
+    ///
+    /// # Errors
+    ///
+    /// Fails if no encoder is available or serializing or writing the compressor fails
+    #[tracing::instrument(skip_all)]
+    pub fn store_with_index(&self, output: impl std::io::Write) -> Result<(), Error> {
+        let encoder = self.encoder.as_ref().ok_or(Error::NoEncoderAvailable)?;
+        self.store_parts(output, Some(&encoder.index))
+    }
+
+    fn store_parts(
+        &self,
+        output: impl std::io::Write,
+        index: Option<&index::Index>,
+    ) -> Result<(), Error> {
         let mut zstd_encoder = zstd::stream::write::Encoder::new(output, 6)?;
         bincode::serialize_into(&mut zstd_encoder, &self.dict)?;
         bincode::serialize_into(&mut zstd_encoder, &self.config)?;
         bincode::serialize_into(&mut zstd_encoder, &self.decoder)?;
+        let checksums = (self.fingerprint.dict, self.config_checksum()?);
+        bincode::serialize_into(&mut zstd_encoder, &checksums)?;
+        if let Some(index) = index {
+            let mut index_writer = frame::ChecksumWriter::new(&mut zstd_encoder);
+            bincode::serialize_into(&mut index_writer, index)?;
+            let index_checksum = index_writer.checksum();
+            bincode::serialize_into(&mut zstd_encoder, &index_checksum)?;
+        }
         zstd_encoder.do_finish()?;
         Ok(())
     }
 
+    fn config_checksum(&self) -> Result<u64, Error> {
+        let config = bincode::serialize(&(&self.config, &self.decoder))?;
+        Ok(frame::metrohash64(&config))
+    }
+
+    /// Load the dict, config and decoder written by `store_parts` and verify their checksums
+    fn load_parts(mut input: impl std::io::Read) -> Result<Self, Error> {
+        let dict: Dictionary = bincode::deserialize_from(&mut input)?;
+        let config: config::Configuration = bincode::deserialize_from(&mut input)?;
+        let decoder: Decoder = bincode::deserialize_from(&mut input)?;
+        let (dict_checksum, config_checksum): (u64, u64) = bincode::deserialize_from(&mut input)?;
+        if dict.fingerprint() != dict_checksum {
+            return Err(Error::ChecksumMismatch {
+                part: ChecksumPart::Dictionary,
+            });
+        }
+        let fingerprint = frame::Fingerprint::new(dict_checksum, &config);
+        let compressor = Self::from_parts(dict, config, None, decoder, fingerprint);
+        if compressor.config_checksum()? != config_checksum {
+            return Err(Error::ChecksumMismatch {
+                part: ChecksumPart::Configuration,
+            });
+        }
+        Ok(compressor)
+    }
+
     /// Load the compressor (dict + config) without the index for encoding from disk
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading or deserializing the compressor fails or the stored checksums
+    /// do not match
     #[tracing::instrument(skip_all)]
     pub fn load(input: impl std::io::Read) -> Result<Self, Error> {
+        let zstd_decoder = zstd::stream::read::Decoder::new(input)?;
+        Self::load_parts(zstd_decoder)
+    }
+
+    /// Load the compressor (dict + config) and the index for encoding stored by
+    /// [`RlzCompressor::store_with_index`]
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading or deserializing the compressor fails, the stored checksums do not
+    /// match, no index was stored or the index does not belong to the dictionary
+    #[tracing::instrument(skip_all)]
+    pub fn load_with_index(input: impl std::io::Read) -> Result<Self, Error> {
         let mut zstd_decoder = zstd::stream::read::Decoder::new(input)?;
-        let dict: Dictionary = bincode::deserialize_from(&mut zstd_decoder)?;
-        let config: config::Configuration = bincode::deserialize_from(&mut zstd_decoder)?;
-        let decoder: Decoder = bincode::deserialize_from(&mut zstd_decoder)?;
-        Ok(Self {
-            dict,
-            config,
-            decoder,
-            encoder: None,
-        })
+        let mut compressor = Self::load_parts(&mut zstd_decoder)?;
+        let mut index_reader = frame::ChecksumReader::new(&mut zstd_decoder);
+        let index: index::Index = bincode::deserialize_from(&mut index_reader)?;
+        let index_checksum = index_reader.checksum();
+        if bincode::deserialize_from::<_, u32>(&mut zstd_decoder)? != index_checksum {
+            return Err(Error::ChecksumMismatch {
+                part: ChecksumPart::Index,
+            });
+        }
+        if !index.is_valid_for(&compressor.dict) {
+            return Err(Error::InvalidIndex);
+        }
+        compressor.encoder = Some(Encoder::from_index(
+            index,
+            &compressor.dict,
+            &compressor.config,
+        ));
+        Ok(compressor)
+    }
+
+    /// Store the compressor (dict + config), and the index for encoding if available, in a raw
+    /// uncompressed layout which [`RlzCompressor::open_mapped`] can memory map. The layout is
+    /// not portable between platforms of different endianness, use [`RlzCompressor::store`]
+    /// for a portable compressed format.
+    ///
+    /// # Errors
+    ///
+    /// Fails if serializing or writing the compressor fails
+    #[tracing::instrument(skip_all)]
+    pub fn store_mapped(&self, output: impl std::io::Write) -> Result<(), Error> {
+        let index = self.encoder.as_ref().map(|encoder| &encoder.index);
+        mapped::write(
+            output,
+            &self.dict,
+            self.fingerprint.dict,
+            &self.config,
+            &self.decoder,
+            index,
+        )
+    }
+
+    /// Load a compressor stored by [`RlzCompressor::store_mapped`] from `data` without
+    /// copying the dictionary or the index
+    ///
+    /// # Errors
+    ///
+    /// Fails if `data` does not contain a compressor in the mapped layout or the stored index
+    /// does not belong to the dictionary
+    #[tracing::instrument(skip_all)]
+    pub fn load_mapped(data: bytes::Bytes) -> Result<Self, Error> {
+        let parts = mapped::read(data)?;
+        let encoder = parts
+            .index
+            .map(|index| Encoder::from_index(index, &parts.dict, &parts.config));
+        let fingerprint = frame::Fingerprint::new(parts.dict_fingerprint, &parts.config);
+        Ok(Self::from_parts(
+            parts.dict,
+            parts.config,
+            encoder,
+            parts.decoder,
+            fingerprint,
+        ))
+    }
+
+    /// Memory map a compressor stored by [`RlzCompressor::store_mapped`]. The mapping is
+    /// read-only and shared with all other processes mapping the same file. The file must
+    /// not be modified while the compressor is in use.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can not be mapped or does not contain a compressor in the mapped layout
+    #[tracing::instrument(skip_all)]
+    pub fn open_mapped(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: the mapping is read-only and the caller guarantees the file is not modified
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        Self::load_mapped(bytes::Bytes::from_owner(mmap))
     }
 
     /// Load the compressor (dict + config) and rebuild the index for encoding
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading or deserializing the compressor fails
     #[tracing::instrument(skip_all)]
     pub fn load_and_build_encoder(&self, input: impl std::io::Read) -> Result<Self, Error> {
         let mut new_compressor = Self::load(input)?;
@@ -139,30 +541,86 @@ pub struct RlzBuilder {
 }
 
 impl RlzBuilder {
-    /// Specificy the minimum length of a factor
+    /// Specificy the minimum length of a factor. At least 1, as the decoder tells single
+    /// byte literals without a dictionary match apart from copies by their length.
     #[must_use]
     pub fn literal_threshold(mut self, threshold: u32) -> RlzBuilder {
-        self.config.literal_threshold = threshold;
+        self.config.literal_threshold = threshold.max(1);
         self
     }
 
-    /// Sepcify the compression codec used for compressing factors
+    /// Specify how documents are split into factors
+    #[must_use]
+    pub fn parsing_strategy(mut self, strategy: ParsingStrategy) -> RlzBuilder {
+        self.config.parsing = strategy;
+        self
+    }
+
+    /// Specify the compression codecs used for compressing factors
     #[must_use]
     pub fn factor_coder(mut self, factor_coder: coder::Coder) -> RlzBuilder {
         self.config.factor_compression = factor_coder;
         self
     }
 
+    /// Specify the codec used for compressing the literal stream
+    #[must_use]
+    pub fn literal_codec(mut self, codec: coder::Codec) -> RlzBuilder {
+        self.config.factor_compression = self.config.factor_compression.literals(codec);
+        self
+    }
+
+    /// Specify the codec used for compressing the offset stream
+    #[must_use]
+    pub fn offset_codec(mut self, codec: coder::Codec) -> RlzBuilder {
+        self.config.factor_compression = self.config.factor_compression.offsets(codec);
+        self
+    }
+
+    /// Specify the codec used for compressing the length stream
+    #[must_use]
+    pub fn len_codec(mut self, codec: coder::Codec) -> RlzBuilder {
+        self.config.factor_compression = self.config.factor_compression.lens(codec);
+        self
+    }
+
+    /// Store checksums of the compressed streams and the decoded document and verify
+    /// them when decoding
+    #[must_use]
+    pub fn checksums(mut self, enabled: bool) -> RlzBuilder {
+        self.config.factor_compression = self.config.factor_compression.checksums(enabled);
+        self
+    }
+
+    /// Specify the width of the dictionary index. Dictionaries larger than 2 GiB always
+    /// use [`IndexWidth::Wide`].
+    #[must_use]
+    pub fn index_width(mut self, width: IndexWidth) -> RlzBuilder {
+        self.config.index_width = width;
+        self
+    }
+
     /// build RLZ compressor from config and dictionary
     pub fn build_from_dict(self, dict: Dictionary) -> RlzCompressor {
-        let encoder = Encoder::build(&dict, &self.config);
-        let decoder = Decoder::from_config(&self.config);
-        RlzCompressor {
-            config: self.config,
-            encoder: Some(encoder),
-            decoder,
-            dict,
+        let mut compressor = self.build_decoder_from_dict(dict);
+        compressor.encoder = Some(Encoder::build(&compressor.dict, &compressor.config));
+        compressor
+    }
+
+    /// build RLZ compressor from config and dictionary without building the index needed
+    /// for encoding. The compressor can decode and be stored with [`RlzCompressor::store`];
+    /// [`RlzCompressor::enable_encode`] builds the index later.
+    pub fn build_decoder_from_dict(mut self, dict: Dictionary) -> RlzCompressor {
+        if dict.len() > index::MAX_COMPACT_DICT_LEN && self.config.index_width != IndexWidth::Wide {
+            tracing::info!(
+                dict_len = dict.len(),
+                "dictionary too large for compact index"
+            );
+            self.config.index_width = IndexWidth::Wide;
         }
+        let decoder = Decoder::from_config(&self.config);
+        let fingerprint = frame::Fingerprint::new(dict.fingerprint(), &self.config);
+        RlzCompressor::from_parts(dict, self.config, None, decoder, fingerprint)
     }
 }
 
@@ -183,6 +641,222 @@ mod tests {
             let encoded_len = rlz_compressor.encode(&text[..],&mut output)?;
             assert_eq!(encoded_len,output.len());
 
+            assert_eq!(rlz_compressor.decoded_len(&output[..])?,text.len());
+            let mut recovered = Vec::new();
+            let decoded_len = rlz_compressor.decode(&output[..],&mut recovered)?;
+
+            assert_eq!(decoded_len,text.len());
+            assert_eq!(recovered,text);
+        }
+    }
+
+    #[test]
+    fn store_compressor_built_without_encoder() {
+        let dict = Dictionary::from(&b"banana$aba$bandana"[..]);
+        let builder = || {
+            RlzCompressor::builder()
+                .literal_threshold(2)
+                .checksums(true)
+        };
+        let decoder_only = builder().build_decoder_from_dict(dict.clone());
+        assert!(decoder_only.encoder.is_none());
+        let mut stored = Vec::new();
+        decoder_only.store(&mut stored).unwrap();
+
+        let full = builder().build_from_dict(dict);
+        let mut stored_full = Vec::new();
+        full.store(&mut stored_full).unwrap();
+        assert_eq!(stored, stored_full);
+
+        let mut encoded = Vec::new();
+        full.encode(&b"bananas"[..], &mut encoded).unwrap();
+        let loaded = RlzCompressor::load(&stored[..]).unwrap();
+        let mut decoded = Vec::new();
+        loaded.decode(&encoded, &mut decoded).unwrap();
+        assert_eq!(decoded, b"bananas");
+    }
+
+    proptest! {
+        #[test]
+        fn encode_and_decode_into(dict: Vec<u8>,text: Vec<u8>,checksums: bool,spare in 0..16usize) {
+            let dict = Dictionary::from(&dict[..]);
+            let rlz_compressor = RlzCompressor::builder().checksums(checksums).build_from_dict(dict);
+            let mut output = Vec::new();
+            rlz_compressor.encode(&text[..],&mut output)?;
+
+            let mut recovered = vec![0; rlz_compressor.decoded_len(&output)? + spare];
+            let decoded_len = rlz_compressor.decode_into(&output,&mut recovered)?;
+            assert_eq!(&recovered[..decoded_len],&text[..]);
+
+            if !text.is_empty() {
+                let result = rlz_compressor.decode_into(&output,&mut recovered[..text.len() - 1]);
+                assert!(matches!(result, Err(Error::OutputTooSmall { .. })));
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn encode_and_decode_batch(dict: Vec<u8>,texts: Vec<Vec<u8>>,num_threads in 1..4usize) {
+            let dict = Dictionary::from(&dict[..]);
+            let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+            let pool = BatchPool::new(num_threads)?;
+
+            let encoded = rlz_compressor.encode_batch(&texts, &pool)?;
+            for (text, encoded) in texts.iter().zip(&encoded) {
+                let mut output = Vec::new();
+                rlz_compressor.encode(&text[..],&mut output)?;
+                assert_eq!(encoded,&output);
+            }
+
+            let decoded = rlz_compressor.decode_batch(&encoded, &pool)?;
+            assert_eq!(decoded,texts);
+        }
+    }
+
+    #[test]
+    fn decode_batch_rejects_corrupt_decoded_len() {
+        let dict = Dictionary::from(&b"banana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let pool = BatchPool::new(2).unwrap();
+        let mut encoded = rlz_compressor
+            .encode_batch([&b"banana$aba"[..], &b"ananas"[..]], &pool)
+            .unwrap();
+
+        // the decoded length directly follows magic, version, flags and fingerprints
+        encoded[1][18..26].copy_from_slice(&u64::MAX.to_le_bytes());
+        let result = rlz_compressor.decode_batch(&encoded, &pool);
+        assert!(matches!(
+            result,
+            Err(Error::DecodedLengthMismatch {
+                expected: u64::MAX,
+                found: 6
+            })
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn encode_with_stats(dict: Vec<u8>,texts: Vec<Vec<u8>>) {
+            let dict = Dictionary::from(&dict[..]);
+            let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+
+            let mut total = EncodeStats::default();
+            for text in &texts {
+                let mut output = Vec::new();
+                let (encoded_len, stats) = rlz_compressor.encode_with_stats(&text[..],&mut output)?;
+                assert_eq!(encoded_len,output.len());
+                assert_eq!(stats.stream_sizes,rlz_compressor.stream_sizes(&output)?);
+                assert_eq!(stats.decoded_bytes,text.len());
+                assert_eq!(stats.factors(),rlz_compressor.factor_lens(&output)?.len());
+                assert!(stats.covered_bytes() <= rlz_compressor.dictionary().len());
+
+                let mut plain = Vec::new();
+                rlz_compressor.encode(&text[..],&mut plain)?;
+                assert_eq!(plain,output);
+                total.merge(&stats);
+            }
+            assert_eq!(total.documents,texts.len());
+            assert_eq!(total.decoded_bytes,texts.iter().map(Vec::len).sum::<usize>());
+        }
+    }
+
+    #[test]
+    fn literal_threshold_is_at_least_one() {
+        let builder = RlzCompressor::builder().literal_threshold(0);
+        assert_eq!(builder.config.literal_threshold, 1);
+        let builder = RlzCompressor::builder().literal_threshold(5);
+        assert_eq!(builder.config.literal_threshold, 5);
+    }
+
+    #[test]
+    fn factor_lens() {
+        let dict = Dictionary::from(&b"banana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let mut output = Vec::new();
+        rlz_compressor
+            .encode(&b"banana$aba"[..], &mut output)
+            .unwrap();
+
+        let lens = rlz_compressor.factor_lens(&output).unwrap();
+        assert_eq!(lens.iter().sum::<u32>(), 10);
+        assert_eq!(lens[0], 6);
+        assert_eq!(rlz_compressor.dictionary().len(), 6);
+        assert_eq!(rlz_compressor.config().literal_threshold, 3);
+    }
+
+    #[test]
+    fn detect_wrong_decoded_len() {
+        let dict = Dictionary::from(&b"banana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let mut output = Vec::new();
+        rlz_compressor
+            .encode(&b"banana$aba"[..], &mut output)
+            .unwrap();
+
+        // the decoded length directly follows magic, version, flags and fingerprints
+        output[18] += 1;
+        assert_eq!(rlz_compressor.decoded_len(&output).unwrap(), 11);
+        let result = rlz_compressor.decode(&output, &mut Vec::new());
+        assert!(matches!(
+            result,
+            Err(Error::DecodedLengthMismatch {
+                expected: 11,
+                found: 10
+            })
+        ));
+        let result = rlz_compressor.decode_into(&output, &mut [0; 11]);
+        assert!(matches!(
+            result,
+            Err(Error::DecodedLengthMismatch {
+                expected: 11,
+                found: 10
+            })
+        ));
+
+        output[18] -= 2;
+        let result = rlz_compressor.decode_into(&output, &mut [0; 11]);
+        assert!(matches!(
+            result,
+            Err(Error::DecodedLengthMismatch {
+                expected: 9,
+                found: 10
+            })
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn decode_range(dict: Vec<u8>,text: Vec<u8>,start: usize,end: usize,literal_threshold in 1..8u32) {
+            let dict = Dictionary::from(&dict[..]);
+            let rlz_compressor = RlzCompressor::builder().literal_threshold(literal_threshold).build_from_dict(dict);
+            let mut output = Vec::new();
+            rlz_compressor.encode(&text[..], &mut output)?;
+
+            let (start, end) = (start % (text.len() + 1), end % (text.len() + 1));
+            let (start, end) = (start.min(end), start.max(end));
+            let mut recovered = Vec::new();
+            let written = rlz_compressor.decode_range(&output, start..end, &mut recovered)?;
+            assert_eq!(written, end - start);
+            assert_eq!(&recovered[..], &text[start..end]);
+
+            let out_of_bounds = rlz_compressor.decode_range(&output, start..text.len() + 1, &mut Vec::new());
+            assert!(matches!(out_of_bounds, Err(Error::RangeOutOfBounds { .. })));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn encode_and_decode_literal_threshold(dict: Vec<u8>,text: Vec<u8>,literal_threshold in 0..8u32) {
+            let dict = Dictionary::from(&dict[..]);
+
+            let rlz_compressor = RlzCompressor::builder()
+                .literal_threshold(literal_threshold)
+                .build_from_dict(dict);
+
+            let mut output = Vec::new();
+            rlz_compressor.encode(&text[..],&mut output)?;
+
             let mut recovered = Vec::new();
             rlz_compressor.decode(&output[..],&mut recovered)?;
 
@@ -213,4 +887,362 @@ mod tests {
             assert_eq!(recovered,text);
         }
     }
+
+    proptest! {
+        #[test]
+        fn store_and_load_with_index(dict: Vec<u8>,text: Vec<u8>) {
+            let dict = Dictionary::from(&dict[..]);
+
+            let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+
+            let mut output = Vec::new();
+            rlz_compressor.encode(&text[..],&mut output)?;
+
+            let mut stored = Vec::new();
+            rlz_compressor.store_with_index(&mut stored)?;
+
+            let loaded = RlzCompressor::load_with_index(&stored[..])?;
+            let mut loaded_output = Vec::new();
+            loaded.encode(&text[..],&mut loaded_output)?;
+            assert_eq!(&loaded_output,&output);
+
+            let loaded_without_index = RlzCompressor::load(&stored[..])?;
+            let mut recovered = Vec::new();
+            loaded_without_index.decode(&loaded_output[..],&mut recovered)?;
+            assert_eq!(recovered,text);
+
+            let mut stored_without_index = Vec::new();
+            rlz_compressor.store(&mut stored_without_index)?;
+            assert!(RlzCompressor::load_with_index(&stored_without_index[..]).is_err());
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn store_and_load_mapped(dict: Vec<u8>,text: Vec<u8>) {
+            let dict = Dictionary::from(&dict[..]);
+
+            let mut rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+
+            let mut output = Vec::new();
+            rlz_compressor.encode(&text[..],&mut output)?;
+
+            let mut stored = Vec::new();
+            rlz_compressor.store_mapped(&mut stored)?;
+            let loaded = RlzCompressor::load_mapped(stored.into())?;
+
+            let mut loaded_output = Vec::new();
+            loaded.encode(&text[..],&mut loaded_output)?;
+            assert_eq!(&loaded_output,&output);
+
+            let mut recovered = Vec::new();
+            loaded.decode(&loaded_output[..],&mut recovered)?;
+            assert_eq!(recovered,text);
+
+            rlz_compressor.encoder = None;
+            let mut stored_without_index = Vec::new();
+            rlz_compressor.store_mapped(&mut stored_without_index)?;
+            let loaded = RlzCompressor::load_mapped(stored_without_index.into())?;
+            assert!(loaded.encoder.is_none());
+        }
+    }
+
+    #[test]
+    fn open_mapped_file() {
+        let dict = Dictionary::from(&b"banana$aba"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+
+        let path = std::env::temp_dir().join(format!("rlz-open-mapped-{}", std::process::id()));
+        rlz_compressor
+            .store_mapped(std::fs::File::create(&path).unwrap())
+            .unwrap();
+        let mapped = RlzCompressor::open_mapped(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(mapped.fingerprint, rlz_compressor.fingerprint);
+
+        let text = b"bananas and abacus";
+        let mut output = Vec::new();
+        mapped.encode(&text[..], &mut output).unwrap();
+        let mut recovered = Vec::new();
+        mapped.decode(&output[..], &mut recovered).unwrap();
+        assert_eq!(recovered, text);
+    }
+
+    #[test]
+    fn reject_truncated_mapped() {
+        let dict = Dictionary::from(&b"banana$aba"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let mut stored = Vec::new();
+        rlz_compressor.store_mapped(&mut stored).unwrap();
+        for len in 0..stored.len() {
+            let truncated = bytes::Bytes::copy_from_slice(&stored[..len]);
+            assert!(RlzCompressor::load_mapped(truncated).is_err());
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn encode_and_decode_optimal(dict: Vec<u8>,text: Vec<u8>) {
+            let dict = Dictionary::from(&dict[..]);
+
+            let rlz_compressor = RlzCompressor::builder()
+                .parsing_strategy(ParsingStrategy::Optimal)
+                .build_from_dict(dict);
+
+            let mut output = Vec::new();
+            rlz_compressor.encode(&text[..],&mut output)?;
+
+            let mut recovered = Vec::new();
+            rlz_compressor.decode(&output[..],&mut recovered)?;
+
+            assert_eq!(recovered,text);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn encode_store_and_decode_per_stream_codecs(dict: Vec<u8>,text: Vec<u8>) {
+            let dict = Dictionary::from(&dict[..]);
+
+            let rlz_compressor = RlzCompressor::builder()
+                .literal_codec(coder::Codec::zstd(19))
+                .offset_codec(coder::Codec::bitpack())
+                .len_codec(coder::Codec::vbyte_zstd(3))
+                .build_from_dict(dict);
+
+            let mut output = Vec::new();
+            rlz_compressor.encode(&text[..],&mut output)?;
+            assert_eq!(rlz_compressor.stream_sizes(&output)?.total(),output.len());
+
+            let mut stored_decoder = Vec::new();
+            rlz_compressor.store(&mut stored_decoder)?;
+
+            let loaded_decoder = RlzCompressor::load(&stored_decoder[..])?;
+
+            let mut recovered = Vec::new();
+            loaded_decoder.decode(&output[..],&mut recovered)?;
+
+            assert_eq!(recovered,text);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn encode_store_and_decode_wide(dict: Vec<u8>,text: Vec<u8>,offset_codec in prop_oneof![Just(coder::Codec::zstd(3)), Just(coder::Codec::vbyte()), Just(coder::Codec::vbyte_zstd(3)), Just(coder::Codec::bitpack())]) {
+            let dict = Dictionary::from(&dict[..]);
+
+            let rlz_compressor = RlzCompressor::builder()
+                .index_width(IndexWidth::Wide)
+                .offset_codec(offset_codec)
+                .build_from_dict(dict);
+
+            let mut output = Vec::new();
+            rlz_compressor.encode(&text[..],&mut output)?;
+
+            let mut stored = Vec::new();
+            rlz_compressor.store_mapped(&mut stored)?;
+            let loaded = RlzCompressor::load_mapped(bytes::Bytes::from(stored))?;
+
+            let mut reencoded = Vec::new();
+            loaded.encode(&text[..],&mut reencoded)?;
+            assert_eq!(&reencoded,&output);
+
+            let mut recovered = Vec::new();
+            loaded.decode(&output[..],&mut recovered)?;
+
+            assert_eq!(recovered,text);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn reject_mismatched_compressor(dict: Vec<u8>,other_dict: Vec<u8>,text: Vec<u8>) {
+            prop_assume!(dict != other_dict);
+            let rlz_compressor = RlzCompressor::builder().build_from_dict(Dictionary::from(&dict[..]));
+
+            let mut output = Vec::new();
+            rlz_compressor.encode(&text[..],&mut output)?;
+
+            let other_dict = RlzCompressor::builder().build_from_dict(Dictionary::from(&other_dict[..]));
+            let result = other_dict.decode(&output[..], &mut Vec::new());
+            assert!(matches!(result, Err(Error::DictionaryMismatch { .. })));
+
+            let other_threshold = RlzCompressor::builder()
+                .literal_threshold(5)
+                .build_from_dict(Dictionary::from(&dict[..]));
+            let result = other_threshold.decode(&output[..], &mut Vec::new());
+            assert!(matches!(result, Err(Error::ConfigurationMismatch)));
+
+            let result = rlz_compressor.decode(&output[1..], &mut Vec::new());
+            assert!(matches!(result, Err(Error::InvalidFrame)));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn detect_corrupt_document(dict: Vec<u8>,text: Vec<u8>,flip: usize) {
+            let dict = Dictionary::from(&dict[..]);
+            let rlz_compressor = RlzCompressor::builder().checksums(true).build_from_dict(dict);
+
+            let mut output = Vec::new();
+            rlz_compressor.encode(&text[..],&mut output)?;
+            assert_eq!(rlz_compressor.stream_sizes(&output)?.total(),output.len());
+
+            let mut recovered = Vec::new();
+            rlz_compressor.decode(&output[..],&mut recovered)?;
+            assert_eq!(recovered,text);
+
+            let header = rlz_compressor.stream_sizes(&output)?.header;
+            prop_assume!(output.len() > header);
+            let flip = header + flip % (output.len() - header);
+            output[flip] ^= 1;
+            let result = rlz_compressor.decode(&output[..],&mut Vec::new());
+            assert!(matches!(result, Err(Error::ChecksumMismatch { .. })));
+        }
+    }
+
+    #[test]
+    fn detect_corrupt_stored_dictionary() {
+        let dict = Dictionary::from(&b"banana bandana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+
+        let mut stored = Vec::new();
+        let mut zstd_encoder = zstd::stream::write::Encoder::new(&mut stored, 6).unwrap();
+        bincode::serialize_into(&mut zstd_encoder, &Dictionary::from(&b"banana bandanA"[..]))
+            .unwrap();
+        bincode::serialize_into(&mut zstd_encoder, &rlz_compressor.config).unwrap();
+        bincode::serialize_into(&mut zstd_encoder, &rlz_compressor.decoder).unwrap();
+        let checksums = (
+            rlz_compressor.fingerprint.dict,
+            rlz_compressor.config_checksum().unwrap(),
+        );
+        bincode::serialize_into(&mut zstd_encoder, &checksums).unwrap();
+        zstd_encoder.do_finish().unwrap();
+
+        let result = RlzCompressor::load(&stored[..]);
+        assert!(matches!(
+            result,
+            Err(Error::ChecksumMismatch {
+                part: ChecksumPart::Dictionary
+            })
+        ));
+    }
+
+    #[test]
+    fn detect_corrupt_stored_index() {
+        let dict = Dictionary::from(&b"banana bandana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let mut stored = Vec::new();
+        rlz_compressor.store_with_index(&mut stored).unwrap();
+
+        // the index follows the payload written by `store`, its first position follows
+        // the variant and the number of positions
+        let mut without_index = Vec::new();
+        rlz_compressor.store(&mut without_index).unwrap();
+        let first_position = zstd::decode_all(&without_index[..]).unwrap().len() + 4 + 8;
+        let mut payload = zstd::decode_all(&stored[..]).unwrap();
+        payload[first_position] ^= 1;
+        let stored = zstd::encode_all(&payload[..], 6).unwrap();
+
+        let result = RlzCompressor::load_with_index(&stored[..]);
+        assert!(matches!(
+            result,
+            Err(Error::ChecksumMismatch {
+                part: ChecksumPart::Index
+            })
+        ));
+    }
+
+    #[test]
+    fn reject_index_of_other_dictionary() {
+        let dict = Dictionary::from(&b"banana bandana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let other_dict = Dictionary::from(&b"banana bandana bonanza"[..]);
+        let other_compressor = RlzCompressor::builder().build_from_dict(other_dict);
+
+        let mut stored = Vec::new();
+        let mut zstd_encoder = zstd::stream::write::Encoder::new(&mut stored, 6).unwrap();
+        bincode::serialize_into(&mut zstd_encoder, &rlz_compressor.dict).unwrap();
+        bincode::serialize_into(&mut zstd_encoder, &rlz_compressor.config).unwrap();
+        bincode::serialize_into(&mut zstd_encoder, &rlz_compressor.decoder).unwrap();
+        let checksums = (
+            rlz_compressor.fingerprint.dict,
+            rlz_compressor.config_checksum().unwrap(),
+        );
+        bincode::serialize_into(&mut zstd_encoder, &checksums).unwrap();
+        let mut index_writer = frame::ChecksumWriter::new(&mut zstd_encoder);
+        let other_index = &other_compressor.encoder.as_ref().unwrap().index;
+        bincode::serialize_into(&mut index_writer, other_index).unwrap();
+        let index_checksum = index_writer.checksum();
+        bincode::serialize_into(&mut zstd_encoder, &index_checksum).unwrap();
+        zstd_encoder.do_finish().unwrap();
+
+        let result = RlzCompressor::load_with_index(&stored[..]);
+        assert!(matches!(result, Err(Error::InvalidIndex)));
+    }
+
+    #[test]
+    fn mapped_position_out_of_bounds() {
+        let dict = Dictionary::from(&b"banana$aba"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let mut stored = Vec::new();
+        rlz_compressor.store_mapped(&mut stored).unwrap();
+        let len = stored.len();
+
+        // the suffix array positions are the last section, they are not read on load
+        let mut out_of_bounds = stored.clone();
+        for position in out_of_bounds[len - 40..].chunks_exact_mut(4) {
+            position.copy_from_slice(&u32::MAX.to_ne_bytes());
+        }
+        // swap the in range positions of `banana$aba` (entry 7) and `nana$aba` (entry 9), both
+        // continue with `n` after their first two bytes but only the first one starts with `ba`
+        let mut swapped = stored;
+        let (head, tail) = swapped[len - 40..].split_at_mut(9 * 4);
+        head[7 * 4..8 * 4].swap_with_slice(tail);
+
+        let text = b"banana bandana";
+        for corrupt in [out_of_bounds, swapped] {
+            let mapped = RlzCompressor::load_mapped(bytes::Bytes::from(corrupt)).unwrap();
+            let mut output = Vec::new();
+            mapped.encode(&text[..], &mut output).unwrap();
+            let mut decoded = Vec::new();
+            mapped.decode(&output, &mut decoded).unwrap();
+            assert_eq!(decoded, text);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn decode_corrupt_input(dict: Vec<u8>, text: Vec<u8>, payload: Vec<u8>, position: usize, byte: u8) {
+            let dict = Dictionary::from(&dict[..]);
+            for factor_coder in [coder::Coder::default(), coder::Coder::new(coder::Codec::raw()), coder::Coder::new(coder::Codec::bitpack())] {
+                let rlz_compressor = RlzCompressor::builder().factor_coder(factor_coder).build_from_dict(dict.clone());
+                let mut output = Vec::new();
+                rlz_compressor.encode(&text[..],&mut output)?;
+                let header_len = rlz_compressor.stream_sizes(&output)?.header;
+
+                // none of these may panic, errors are expected
+                let mut replaced = output[..header_len].to_vec();
+                replaced.extend_from_slice(&payload);
+                let _ = rlz_compressor.decode(&replaced, &mut Vec::new());
+
+                let _ = rlz_compressor.decode(&output[..position % (output.len() + 1)], &mut Vec::new());
+
+                let mut modified = output.clone();
+                let position = position % modified.len();
+                modified[position] = byte;
+                let _ = rlz_compressor.decode(&modified, &mut Vec::new());
+                let _ = rlz_compressor.decode_range(&modified, position..text.len(), &mut Vec::new());
+                let _ = rlz_compressor.decode_into(&modified, &mut vec![0; text.len()]);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn decode_arbitrary_input(input: Vec<u8>) {
+            let rlz_compressor = RlzCompressor::builder().build_from_dict(Dictionary::from(&b"banana"[..]));
+            let _ = rlz_compressor.decode(&input, &mut Vec::new());
+        }
+    }
 }
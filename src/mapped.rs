@@ -0,0 +1,128 @@
+//! Raw, uncompressed layout of a compressor which can be memory mapped and shared
+//! read-only between processes. All sections are stored in native endianness and
+//! aligned to 8 bytes:
+//!
+//! ```text
+//! [magic: 8][version: u32][endianness: u32][header len: u64][dict len: u64][sa len: u64]
+//! [header (config, decoder, dict fingerprint, sa width + buckets)][pad][dict][pad]
+//! [sa: sa len x u32 or u64]
+//! ```
+use bytes::{Buf, Bytes};
+use serde::{Deserialize, Serialize};
+
+use crate::{config, decoder::Decoder, index, Dictionary, Error};
+
+const MAGIC: &[u8; 8] = b"RLZMMAP\0";
+const VERSION: u32 = 3;
+const ENDIANNESS: u32 = 0x0102_0304;
+const ALIGNMENT: usize = 8;
+const PREFIX_BYTES: usize = MAGIC.len() + 2 * 4 + 3 * 8;
+
+#[derive(Serialize)]
+struct HeaderRef<'a> {
+    config: &'a config::Configuration,
+    decoder: &'a Decoder,
+    dict_fingerprint: u64,
+    index: Option<(config::IndexWidth, &'a [index::SuffixArrayRangeInclusive])>,
+}
+
+#[derive(Deserialize)]
+struct Header {
+    config: config::Configuration,
+    decoder: Decoder,
+    dict_fingerprint: u64,
+    index: Option<(config::IndexWidth, Vec<index::SuffixArrayRangeInclusive>)>,
+}
+
+pub(crate) struct MappedParts {
+    pub(crate) dict: Dictionary,
+    pub(crate) config: config::Configuration,
+    pub(crate) decoder: Decoder,
+    /// fingerprint of the dictionary, stored so loading does not have to read the dictionary
+    pub(crate) dict_fingerprint: u64,
+    pub(crate) index: Option<index::Index>,
+}
+
+fn padding(len: usize) -> usize {
+    (ALIGNMENT - len % ALIGNMENT) % ALIGNMENT
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) fn write(
+    mut output: impl std::io::Write,
+    dict: &Dictionary,
+    dict_fingerprint: u64,
+    config: &config::Configuration,
+    decoder: &Decoder,
+    index: Option<&index::Index>,
+) -> Result<(), Error> {
+    let header = bincode::serialize(&HeaderRef {
+        config,
+        decoder,
+        dict_fingerprint,
+        index: index.map(|index| (index.width(), index.buckets())),
+    })?;
+    let num_positions = index.map_or(0, index::Index::len);
+    let positions = index.map_or(&[][..], index::Index::position_bytes);
+
+    output.write_all(MAGIC)?;
+    output.write_all(&VERSION.to_ne_bytes())?;
+    output.write_all(&ENDIANNESS.to_ne_bytes())?;
+    for len in [header.len(), dict.len(), num_positions] {
+        output.write_all(&(len as u64).to_ne_bytes())?;
+    }
+    let zeros = [0u8; ALIGNMENT];
+    output.write_all(&header)?;
+    output.write_all(&zeros[..padding(header.len())])?;
+    output.write_all(dict)?;
+    output.write_all(&zeros[..padding(dict.len())])?;
+    output.write_all(positions)?;
+    output.flush()?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) fn read(data: Bytes) -> Result<MappedParts, Error> {
+    let mut prefix = data.get(..PREFIX_BYTES).ok_or(Error::InvalidMappedLayout)?;
+    let magic = prefix.copy_to_bytes(MAGIC.len());
+    if &magic[..] != MAGIC || prefix.get_u32_ne() != VERSION || prefix.get_u32_ne() != ENDIANNESS {
+        return Err(Error::InvalidMappedLayout);
+    }
+    let mut section_len = || usize::try_from(prefix.get_u64_ne()).ok();
+    let (header_len, dict_len, num_positions) = (section_len(), section_len(), section_len());
+
+    let mut remaining = data;
+    remaining.advance(PREFIX_BYTES);
+    let mut next_section = |len: Option<usize>| {
+        let len = len
+            .filter(|len| *len <= remaining.len())
+            .ok_or(Error::InvalidMappedLayout)?;
+        let section = remaining.split_to(len);
+        remaining.advance(padding(len).min(remaining.len()));
+        Ok::<_, Error>(section)
+    };
+    let header: Header = bincode::deserialize(&next_section(header_len)?)?;
+    // zero-copy as `Bytes` implements `copy_to_bytes` by slicing
+    let dict = Dictionary::from(next_section(dict_len)?);
+
+    let index = match header.index {
+        Some((width, buckets)) => {
+            let positions =
+                next_section(num_positions.and_then(|n| n.checked_mul(width.position_bytes())))?;
+            // only checks the shape of the index, reading all positions would touch the
+            // whole mapping
+            let index = index::Index::from_mapped(positions, width, buckets, &header.config)
+                .filter(|index| index.is_valid_for(&dict))
+                .ok_or(Error::InvalidMappedLayout)?;
+            Some(index)
+        }
+        None => None,
+    };
+    Ok(MappedParts {
+        dict,
+        config: header.config,
+        decoder: header.decoder,
+        dict_fingerprint: header.dict_fingerprint,
+        index,
+    })
+}
@@ -19,30 +19,6 @@ impl Scratch {
         self.offsets.clear();
         self.lens.clear();
     }
-
-    pub fn reserve_encoded(&mut self, bytes: usize) {
-        let bytes = 1024 + bytes * 5; // some safety here..
-        self.encoded.clear();
-        self.encoded.reserve(bytes);
-        unsafe {
-            self.encoded.set_len(bytes);
-        }
-    }
-
-    pub fn reserve_output(&mut self, bytes: usize) {
-        let bytes = 1024 + bytes * 5; // some safety here..
-        self.literals.clear();
-        self.literals.reserve(bytes);
-        self.offsets.clear();
-        self.offsets.reserve(bytes);
-        self.lens.clear();
-        self.lens.reserve(bytes);
-        unsafe {
-            self.literals.set_len(bytes);
-            self.offsets.set_len(bytes);
-            self.lens.set_len(bytes);
-        }
-    }
 }
 
 impl Default for Scratch {
@@ -0,0 +1,221 @@
+use std::ops::Range;
+
+use crate::{coder, factor::FactorType};
+
+/// Number of factor length buckets, bucket `i` counts lengths in `2^(i-1)..2^i`
+pub const LEN_BUCKETS: usize = u32::BITS as usize + 1;
+
+/// Statistics about the factors and streams of encoded documents, collected by
+/// [`RlzCompressor::encode_with_stats`](crate::RlzCompressor::encode_with_stats). Statistics
+/// of many documents are combined with [`EncodeStats::merge`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub struct EncodeStats {
+    /// number of encoded documents
+    pub documents: usize,
+    /// bytes of the documents before encoding
+    pub decoded_bytes: usize,
+    /// number of factors stored as literals
+    pub literal_factors: usize,
+    /// bytes stored as literals
+    pub literal_bytes: usize,
+    /// number of factors copied from the dictionary
+    pub copy_factors: usize,
+    /// bytes copied from the dictionary
+    pub copy_bytes: usize,
+    /// bytes of each stream of the encoded documents
+    pub stream_sizes: coder::StreamSizes,
+    /// number of factors by length, bucket `i` counts lengths in `2^(i-1)..2^i` and bucket
+    /// zero counts empty factors
+    pub len_histogram: [usize; LEN_BUCKETS],
+    /// length of the dictionary the documents were encoded against
+    pub dict_len: usize,
+    /// sorted, disjoint dictionary ranges referenced by copy factors
+    covered: Vec<Range<usize>>,
+}
+
+impl Default for EncodeStats {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl EncodeStats {
+    pub(crate) fn new(dict_len: usize) -> Self {
+        Self {
+            documents: 0,
+            decoded_bytes: 0,
+            literal_factors: 0,
+            literal_bytes: 0,
+            copy_factors: 0,
+            copy_bytes: 0,
+            stream_sizes: coder::StreamSizes::default(),
+            len_histogram: [0; LEN_BUCKETS],
+            dict_len,
+            covered: Vec::new(),
+        }
+    }
+
+    pub(crate) fn add_factor(&mut self, factor: &FactorType) {
+        let len = factor.len();
+        if let FactorType::Copy { offset, .. } = factor {
+            self.covered.push(*offset..*offset + len);
+        }
+        self.add_factor_len(len, matches!(factor, FactorType::Literal(_)));
+    }
+
+    /// Record a factor of `len` bytes stored as a literal or copied from the dictionary.
+    /// Without its dictionary offset the factor does not count towards the coverage.
+    pub fn add_factor_len(&mut self, len: usize, literal: bool) {
+        if literal {
+            self.literal_factors += 1;
+            self.literal_bytes += len;
+        } else {
+            self.copy_factors += 1;
+            self.copy_bytes += len;
+        }
+        self.len_histogram[(usize::BITS - len.leading_zeros()) as usize] += 1;
+        self.decoded_bytes += len;
+    }
+
+    /// Record a completed document encoded into `sizes`
+    pub(crate) fn finish_document(&mut self, sizes: coder::StreamSizes) {
+        self.documents += 1;
+        self.add_sizes(sizes);
+        normalize(&mut self.covered);
+    }
+
+    /// Add the stream sizes of an encoded document or block
+    pub fn add_sizes(&mut self, sizes: coder::StreamSizes) {
+        self.stream_sizes.header += sizes.header;
+        self.stream_sizes.literals += sizes.literals;
+        self.stream_sizes.offsets += sizes.offsets;
+        self.stream_sizes.lens += sizes.lens;
+    }
+
+    /// Add the statistics of `other` to these statistics
+    pub fn merge(&mut self, other: &EncodeStats) {
+        self.documents += other.documents;
+        self.decoded_bytes += other.decoded_bytes;
+        self.literal_factors += other.literal_factors;
+        self.literal_bytes += other.literal_bytes;
+        self.copy_factors += other.copy_factors;
+        self.copy_bytes += other.copy_bytes;
+        self.add_sizes(other.stream_sizes);
+        for (bucket, count) in self.len_histogram.iter_mut().zip(other.len_histogram) {
+            *bucket += count;
+        }
+        self.dict_len = self.dict_len.max(other.dict_len);
+        self.covered.extend_from_slice(&other.covered);
+        normalize(&mut self.covered);
+    }
+
+    /// Total number of factors
+    #[must_use]
+    pub fn factors(&self) -> usize {
+        self.literal_factors + self.copy_factors
+    }
+
+    /// Average number of bytes per factor
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn average_factor_len(&self) -> f64 {
+        self.decoded_bytes as f64 / self.factors().max(1) as f64
+    }
+
+    /// Ratio of the decoded to the encoded size
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn compression_ratio(&self) -> f64 {
+        self.decoded_bytes as f64 / self.stream_sizes.total().max(1) as f64
+    }
+
+    /// Number of dictionary bytes referenced by at least one copy factor
+    #[must_use]
+    pub fn covered_bytes(&self) -> usize {
+        self.covered.iter().map(ExactSizeIterator::len).sum()
+    }
+
+    /// Fraction of the dictionary referenced by at least one copy factor
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn dict_coverage(&self) -> f64 {
+        self.covered_bytes() as f64 / self.dict_len.max(1) as f64
+    }
+}
+
+impl std::iter::Sum for EncodeStats {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(EncodeStats::default(), |mut total, stats| {
+            total.merge(&stats);
+            total
+        })
+    }
+}
+
+/// Sort `ranges` and merge overlapping or adjacent ranges
+fn normalize(ranges: &mut Vec<Range<usize>>) {
+    ranges.sort_unstable_by_key(|range| range.start);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges.drain(..) {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    *ranges = merged;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn copy(offset: usize, len: u32) -> FactorType {
+        FactorType::Copy { offset, len }
+    }
+
+    #[test]
+    fn coverage_counts_bytes_once() {
+        let mut stats = EncodeStats::new(100);
+        for factor in [copy(0, 10), copy(5, 10), copy(50, 5)] {
+            stats.add_factor(&factor);
+        }
+        stats.add_factor(&FactorType::Literal(bytes::Bytes::from_static(b"ab")));
+        stats.finish_document(coder::StreamSizes::default());
+
+        assert_eq!(stats.covered_bytes(), 20);
+        assert_eq!(stats.copy_bytes, 25);
+        assert_eq!(stats.factors(), 4);
+        assert_eq!(stats.len_histogram[2], 1);
+        assert_eq!(stats.len_histogram[4], 2);
+        assert!((stats.dict_coverage() - 0.2).abs() < f64::EPSILON);
+    }
+
+    proptest! {
+        #[test]
+        fn merge_matches_single_collection(factors: Vec<(u8, u8)>, split: usize) {
+            let factors: Vec<_> = factors.into_iter().map(|(offset, len)| copy(offset.into(), len.into())).collect();
+            let split = split % (factors.len() + 1);
+
+            let mut single = EncodeStats::new(512);
+            for factor in &factors {
+                single.add_factor(factor);
+            }
+            single.finish_document(coder::StreamSizes::default());
+
+            let mut parts = [EncodeStats::new(512), EncodeStats::new(512)];
+            factors[..split].iter().for_each(|factor| parts[0].add_factor(factor));
+            factors[split..].iter().for_each(|factor| parts[1].add_factor(factor));
+            for part in &mut parts {
+                part.finish_document(coder::StreamSizes::default());
+            }
+            let merged: EncodeStats = parts.into_iter().sum();
+
+            assert_eq!(merged.covered_bytes(), single.covered_bytes());
+            assert_eq!(merged.copy_bytes, single.copy_bytes);
+            assert_eq!(merged.len_histogram, single.len_histogram);
+            assert_eq!(merged.documents, 2);
+        }
+    }
+}
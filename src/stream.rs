@@ -0,0 +1,300 @@
+//! Streaming encoding and decoding of documents too large to hold in memory.
+//!
+//! [`RlzWriter`] splits its input into chunks of at most [`RlzWriter::chunk_size`] bytes
+//! and encodes each chunk as a separate document, so memory use is bounded by the chunk
+//! size instead of the document size. Factors do not span chunks. The layout is
+//!
+//! ```text
+//! [magic][block len: u32][encoded chunk]...[block len: u32][encoded chunk][0: u32]
+//! ```
+//!
+//! where each encoded chunk is the output of [`RlzCompressor::encode`](crate::RlzCompressor::encode)
+//! and all fixed width integers are stored little endian. [`RlzReader`] decodes a stream
+//! on demand, one factor at a time.
+//!
+//! ```rust
+//! use rlz::stream::{RlzReader, RlzWriter};
+//! use rlz::{Dictionary, RlzCompressor};
+//! use std::io::{Read, Write};
+//!
+//! let dict = Dictionary::from(&b"banana"[..]);
+//! let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+//!
+//! let mut writer = RlzWriter::new(&rlz_compressor, Vec::new()).chunk_size(4);
+//! writer.write_all(b"banana$aba").unwrap();
+//! let encoded = writer.finish().unwrap();
+//!
+//! let mut reader = RlzReader::new(&rlz_compressor, &encoded[..]);
+//! let mut recovered = Vec::new();
+//! reader.read_to_end(&mut recovered).unwrap();
+//! assert_eq!(recovered, b"banana$aba");
+//! ```
+mod reader;
+mod writer;
+
+pub use reader::RlzReader;
+pub use writer::RlzWriter;
+
+const MAGIC: &[u8; 4] = b"RLZS";
+const BLOCK_LEN_BYTES: usize = std::mem::size_of::<u32>();
+/// Default number of input bytes encoded per block
+const DEFAULT_CHUNK_SIZE: usize = 1024 * 1024;
+/// Keeps encoded chunks well below the `u32` block length limit
+const MAX_CHUNK_SIZE: usize = 1024 * 1024 * 1024;
+
+/// Iterate over the encoded chunks of a `stream` held in memory, each of which can be
+/// passed to [`RlzCompressor::decode`](crate::RlzCompressor::decode)
+#[must_use]
+pub fn blocks(stream: &[u8]) -> Blocks<'_> {
+    Blocks {
+        stream,
+        started: false,
+        finished: false,
+    }
+}
+
+/// Iterator over the encoded chunks of a stream returned by [`blocks`]
+pub struct Blocks<'s> {
+    stream: &'s [u8],
+    started: bool,
+    finished: bool,
+}
+
+impl<'s> Blocks<'s> {
+    fn next_block(&mut self) -> Result<Option<&'s [u8]>, crate::Error> {
+        if !self.started {
+            self.started = true;
+            self.stream = self
+                .stream
+                .strip_prefix(MAGIC)
+                .ok_or(crate::Error::InvalidStream)?;
+        }
+        let (block_len, rest) = self
+            .stream
+            .split_first_chunk::<BLOCK_LEN_BYTES>()
+            .ok_or(crate::Error::TruncatedInput)?;
+        let block_len = u32::from_le_bytes(*block_len) as usize;
+        if block_len == 0 {
+            return Ok(None);
+        }
+        let (block, rest) = rest
+            .split_at_checked(block_len)
+            .ok_or(crate::Error::TruncatedInput)?;
+        self.stream = rest;
+        Ok(Some(block))
+    }
+}
+
+impl<'s> Iterator for Blocks<'s> {
+    type Item = Result<&'s [u8], crate::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let block = self.next_block();
+        self.finished = !matches!(block, Ok(Some(_)));
+        block.transpose()
+    }
+}
+
+fn into_io_error(error: crate::Error) -> std::io::Error {
+    match error {
+        crate::Error::IOError(error) => error,
+        error => std::io::Error::other(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Dictionary, RlzCompressor};
+    use bytes::Buf;
+    use proptest::prelude::*;
+    use std::io::{Read, Write};
+
+    fn decode_blocks(rlz_compressor: &RlzCompressor, mut stream: &[u8]) -> Vec<u8> {
+        assert_eq!(&stream[..MAGIC.len()], MAGIC);
+        stream.advance(MAGIC.len());
+        let mut decoded = Vec::new();
+        loop {
+            let block_len = stream.get_u32_le() as usize;
+            if block_len == 0 {
+                break;
+            }
+            rlz_compressor
+                .decode(&stream[..block_len], &mut decoded)
+                .unwrap();
+            stream.advance(block_len);
+        }
+        assert!(stream.is_empty());
+        decoded
+    }
+
+    proptest! {
+        #[test]
+        fn write_in_chunks(dict: Vec<u8>, writes: Vec<Vec<u8>>, chunk_size in 1..64usize) {
+            let dict = Dictionary::from(&dict[..]);
+            let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+
+            let mut writer = RlzWriter::new(&rlz_compressor, Vec::new()).chunk_size(chunk_size);
+            for write in &writes {
+                writer.write_all(write)?;
+            }
+            let stream = writer.finish()?;
+            assert_eq!(decode_blocks(&rlz_compressor, &stream), writes.concat());
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn encode_reader(dict: Vec<u8>, text: Vec<u8>) {
+            let dict = Dictionary::from(&dict[..]);
+            let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+
+            let stream = rlz_compressor.encode_reader(&text[..], Vec::new())?;
+            assert_eq!(decode_blocks(&rlz_compressor, &stream), text);
+        }
+    }
+
+    #[test]
+    fn iterate_blocks() {
+        let dict = Dictionary::from(&b"banana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+
+        let mut writer = RlzWriter::new(&rlz_compressor, Vec::new()).chunk_size(4);
+        writer.write_all(b"banana$aba").unwrap();
+        let stream = writer.finish().unwrap();
+
+        let mut decoded = Vec::new();
+        for block in blocks(&stream) {
+            rlz_compressor.decode(block.unwrap(), &mut decoded).unwrap();
+        }
+        assert_eq!(decoded, b"banana$aba");
+        assert_eq!(blocks(&stream).count(), 3);
+
+        assert!(blocks(&stream[..stream.len() - 1]).any(|block| block.is_err()));
+        assert!(matches!(
+            blocks(&stream[1..]).next(),
+            Some(Err(crate::Error::InvalidStream))
+        ));
+    }
+
+    #[test]
+    fn flush_ends_block() {
+        let dict = Dictionary::from(&b"banana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+
+        let mut writer = RlzWriter::new(&rlz_compressor, Vec::new());
+        writer.write_all(b"banana").unwrap();
+        writer.flush().unwrap();
+        writer.write_all(b"$aba").unwrap();
+        let stream = writer.finish().unwrap();
+        assert_eq!(decode_blocks(&rlz_compressor, &stream), b"banana$aba");
+
+        let single_block = rlz_compressor
+            .encode_reader(&b"banana$aba"[..], Vec::new())
+            .unwrap();
+        assert!(single_block.len() < stream.len());
+    }
+
+    proptest! {
+        #[test]
+        fn read_in_pieces(dict: Vec<u8>, text: Vec<u8>, chunk_size in 1..64usize, read_size in 1..16usize, checksums: bool) {
+            let dict = Dictionary::from(&dict[..]);
+            let rlz_compressor = RlzCompressor::builder().checksums(checksums).build_from_dict(dict);
+
+            let mut writer = RlzWriter::new(&rlz_compressor, Vec::new()).chunk_size(chunk_size);
+            writer.write_all(&text)?;
+            let stream = writer.finish()?;
+
+            let mut reader = RlzReader::new(&rlz_compressor, &stream[..]);
+            let mut recovered = Vec::new();
+            let mut buf = vec![0; read_size];
+            loop {
+                let len = reader.read(&mut buf)?;
+                if len == 0 {
+                    break;
+                }
+                recovered.extend_from_slice(&buf[..len]);
+            }
+            assert_eq!(recovered, text);
+            assert!(reader.into_inner().is_empty());
+        }
+    }
+
+    #[test]
+    fn stop_reading_early() {
+        let dict = Dictionary::from(&b"banana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let text = b"banana$aba".repeat(100);
+        let mut writer = RlzWriter::new(&rlz_compressor, Vec::new()).chunk_size(10);
+        writer.write_all(&text).unwrap();
+        let chunked = writer.finish().unwrap();
+
+        let mut reader = RlzReader::new(&rlz_compressor, &chunked[..]);
+        let mut prefix = [0; 15];
+        reader.read_exact(&mut prefix).unwrap();
+        assert_eq!(&prefix, &text[..15]);
+        // only the first two blocks were read from the input
+        let remaining = reader.into_inner();
+        assert!(remaining.len() > chunked.len() / 2);
+    }
+
+    #[test]
+    fn reject_corrupt_stream() {
+        let dict = Dictionary::from(&b"banana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let stream = rlz_compressor
+            .encode_reader(&b"banana$aba"[..], Vec::new())
+            .unwrap();
+
+        for len in 0..stream.len() {
+            let mut reader = RlzReader::new(&rlz_compressor, &stream[..len]);
+            assert!(reader.read_to_end(&mut Vec::new()).is_err());
+        }
+        let mut reader = RlzReader::new(&rlz_compressor, &stream[1..]);
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn return_bytes_before_error() {
+        let dict = Dictionary::from(&b"banana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let text = b"banana$aba".repeat(2);
+        let mut writer = RlzWriter::new(&rlz_compressor, Vec::new()).chunk_size(10);
+        writer.write_all(&text).unwrap();
+        let stream = writer.finish().unwrap();
+
+        // the second block is cut short
+        let mut reader = RlzReader::new(&rlz_compressor, &stream[..stream.len() - 8]);
+        let mut buf = [0; 64];
+        let len = reader.read(&mut buf).unwrap();
+        assert_eq!(&buf[..len], &text[..len]);
+        assert!(len >= 10);
+        let error = reader.read(&mut buf).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::Other);
+    }
+
+    #[test]
+    fn pass_on_io_errors() {
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::ConnectionReset.into())
+            }
+        }
+
+        let dict = Dictionary::from(&b"banana"[..]);
+        let rlz_compressor = RlzCompressor::builder().build_from_dict(dict);
+        let stream = rlz_compressor
+            .encode_reader(&b"banana$aba"[..], Vec::new())
+            .unwrap();
+
+        for len in [0, MAGIC.len(), stream.len() - BLOCK_LEN_BYTES] {
+            let mut reader = RlzReader::new(&rlz_compressor, Read::chain(&stream[..len], Failing));
+            let error = reader.read_to_end(&mut Vec::new()).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::ConnectionReset);
+        }
+    }
+}
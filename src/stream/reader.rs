@@ -0,0 +1,177 @@
+use bytes::{Buf, Bytes};
+use std::io::{Read, Write};
+
+use crate::{decoder::FactorReader, frame, Error, RlzCompressor};
+
+use super::{into_io_error, BLOCK_LEN_BYTES, MAGIC};
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum State {
+    Start,
+    Blocks,
+    Finished,
+}
+
+/// Decodes a stream written by [`RlzWriter`](super::RlzWriter) on demand. Blocks are only
+/// read from `input` and decoded once their bytes are requested, so dropping the reader
+/// early skips decoding the rest of the stream.
+#[allow(clippy::module_name_repetitions)]
+pub struct RlzReader<'c, R: Read> {
+    compressor: &'c RlzCompressor,
+    input: R,
+    state: State,
+    block: Vec<u8>,
+    factors: Option<FactorReader<'c>>,
+    /// decoded bytes of the current factor not returned yet
+    pending: Bytes,
+    /// frame header of the current block
+    header: Option<frame::FrameHeader>,
+    /// bytes decoded from the current block so far
+    decoded: usize,
+    /// checksum of the bytes decoded from the current block if it stores one
+    checksum: Option<frame::ChecksumWriter<std::io::Sink>>,
+    /// error hit after bytes were already returned by `read`, returned by the next call
+    error: Option<std::io::Error>,
+}
+
+impl<'c, R: Read> RlzReader<'c, R> {
+    /// Create a reader which decodes the stream in `input` with `compressor`
+    pub fn new(compressor: &'c RlzCompressor, input: R) -> Self {
+        Self {
+            compressor,
+            input,
+            state: State::Start,
+            block: Vec::new(),
+            factors: None,
+            pending: Bytes::new(),
+            header: None,
+            decoded: 0,
+            checksum: None,
+            error: None,
+        }
+    }
+
+    /// Stop decoding and return the underlying input, positioned after the last block read
+    pub fn into_inner(self) -> R {
+        self.input
+    }
+
+    /// Ensure `pending` holds decoded bytes, returns false at the end of the stream
+    fn fill_pending(&mut self) -> Result<bool, Error> {
+        while self.pending.is_empty() {
+            let Some(factors) = self.factors.as_mut() else {
+                if !self.next_block()? {
+                    return Ok(false);
+                }
+                continue;
+            };
+            match factors.next_bytes(&self.compressor.dict)? {
+                Some(bytes) => {
+                    self.decoded += bytes.len();
+                    if let Some(checksum) = self.checksum.as_mut() {
+                        checksum.write_all(&bytes)?;
+                    }
+                    self.pending = bytes;
+                }
+                None => self.finish_block()?,
+            }
+        }
+        Ok(true)
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn next_block(&mut self) -> Result<bool, Error> {
+        if self.state == State::Start {
+            let mut magic = [0; MAGIC.len()];
+            self.input
+                .read_exact(&mut magic)
+                .map_err(|error| eof_as(error, Error::InvalidStream))?;
+            if &magic != MAGIC {
+                return Err(Error::InvalidStream);
+            }
+            self.state = State::Blocks;
+        }
+        if self.state == State::Finished {
+            return Ok(false);
+        }
+
+        let mut block_len = [0; BLOCK_LEN_BYTES];
+        self.input
+            .read_exact(&mut block_len)
+            .map_err(|error| eof_as(error, Error::TruncatedInput))?;
+        let block_len = u32::from_le_bytes(block_len) as usize;
+        if block_len == 0 {
+            self.state = State::Finished;
+            return Ok(false);
+        }
+        self.block.clear();
+        // not pre-allocating `block_len` guards against corrupt lengths
+        (&mut self.input)
+            .take(block_len as u64)
+            .read_to_end(&mut self.block)?;
+        if self.block.len() < block_len {
+            return Err(Error::TruncatedInput);
+        }
+
+        let mut block = &self.block[..];
+        let header = frame::FrameHeader::read(&mut block)?;
+        header.check(self.compressor.fingerprint)?;
+        self.header = Some(header);
+        self.decoded = 0;
+        self.checksum = header
+            .checksum
+            .map(|_| frame::ChecksumWriter::new(std::io::sink()));
+        self.factors = Some(
+            self.compressor
+                .decoder
+                .factor_reader(&self.compressor.dict, block)?,
+        );
+        Ok(true)
+    }
+
+    fn finish_block(&mut self) -> Result<(), Error> {
+        self.factors = None;
+        if let Some(header) = self.header.take() {
+            if let Some(checksum) = self.checksum.take() {
+                header.verify(checksum.checksum())?;
+            }
+            header.verify_len(self.decoded)?;
+        }
+        Ok(())
+    }
+}
+
+/// Map the end of `input` to `eof`, other I/O errors are passed on
+fn eof_as(error: std::io::Error, eof: Error) -> Error {
+    if error.kind() == std::io::ErrorKind::UnexpectedEof {
+        eof
+    } else {
+        Error::IOError(error)
+    }
+}
+
+impl<R: Read> Read for RlzReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        let mut written = 0;
+        while written < buf.len() {
+            match self.fill_pending() {
+                Ok(true) => {}
+                Ok(false) => break,
+                // the bytes already copied into `buf` are returned first
+                Err(error) if written > 0 => {
+                    self.error = Some(into_io_error(error));
+                    break;
+                }
+                Err(error) => return Err(into_io_error(error)),
+            }
+            let len = self.pending.len().min(buf.len() - written);
+            buf[written..written + len].copy_from_slice(&self.pending[..len]);
+            self.pending.advance(len);
+            written += len;
+        }
+        Ok(written)
+    }
+}
@@ -0,0 +1,108 @@
+use bytes::BytesMut;
+
+use crate::{Error, RlzCompressor};
+
+use super::{into_io_error, BLOCK_LEN_BYTES, DEFAULT_CHUNK_SIZE, MAGIC, MAX_CHUNK_SIZE};
+
+/// Encodes everything written to it in bounded chunks and writes the encoded blocks
+/// to `output`. [`RlzWriter::finish`] must be called to encode the last chunk and
+/// terminate the stream.
+#[allow(clippy::module_name_repetitions)]
+pub struct RlzWriter<'c, W: std::io::Write> {
+    compressor: &'c RlzCompressor,
+    output: W,
+    chunk_size: usize,
+    chunk: BytesMut,
+    encoded: Vec<u8>,
+    header_written: bool,
+}
+
+impl<'c, W: std::io::Write> RlzWriter<'c, W> {
+    /// Create a new stream which encodes with `compressor` and writes to `output`
+    pub fn new(compressor: &'c RlzCompressor, output: W) -> Self {
+        Self {
+            compressor,
+            output,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            chunk: BytesMut::new(),
+            encoded: Vec::new(),
+            header_written: false,
+        }
+    }
+
+    /// Specify the number of input bytes encoded per block, at most 1 GiB
+    #[must_use]
+    pub fn chunk_size(mut self, bytes: usize) -> Self {
+        self.chunk_size = bytes.clamp(1, MAX_CHUNK_SIZE);
+        self
+    }
+
+    /// Append `input` to the stream, encoding every chunk that fills up
+    ///
+    /// # Errors
+    ///
+    /// Fails if the compressor can not encode or writing an encoded block fails
+    pub fn push(&mut self, mut input: &[u8]) -> Result<(), Error> {
+        while !input.is_empty() {
+            let len = input.len().min(self.chunk_size - self.chunk.len());
+            self.chunk.extend_from_slice(&input[..len]);
+            input = &input[len..];
+            if self.chunk.len() == self.chunk_size {
+                self.flush_chunk()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode the buffered input, terminate the stream and return the output
+    ///
+    /// # Errors
+    ///
+    /// Fails if the compressor can not encode or writing to the output fails
+    #[tracing::instrument(skip_all)]
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.flush_chunk()?;
+        self.write_header()?;
+        self.output.write_all(&0u32.to_le_bytes())?;
+        self.output.flush()?;
+        Ok(self.output)
+    }
+
+    fn write_header(&mut self) -> Result<(), Error> {
+        if !self.header_written {
+            self.output.write_all(MAGIC)?;
+            self.header_written = true;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    #[allow(clippy::cast_possible_truncation)]
+    fn flush_chunk(&mut self) -> Result<(), Error> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        self.write_header()?;
+        self.encoded.clear();
+        self.encoded.resize(BLOCK_LEN_BYTES, 0);
+        let encoded_len = self
+            .compressor
+            .encode(self.chunk.split().freeze(), &mut self.encoded)?;
+        self.encoded[..BLOCK_LEN_BYTES].copy_from_slice(&(encoded_len as u32).to_le_bytes());
+        self.output.write_all(&self.encoded)?;
+        Ok(())
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for RlzWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.push(buf).map_err(into_io_error)?;
+        Ok(buf.len())
+    }
+
+    /// Encodes the buffered input as a block even if the chunk is not full yet
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.flush_chunk().map_err(into_io_error)?;
+        self.output.flush()
+    }
+}
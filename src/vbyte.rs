@@ -1,16 +1,62 @@
 use bytes::{Buf, BufMut};
 
-pub fn decode(mut input: impl Buf) -> u32 {
+use crate::Error;
+
+/// Decode a value, failing if `input` ends early or the value does not fit a `u32`
+pub fn decode(mut input: impl Buf) -> Result<u32, Error> {
     let mut val: u32 = 0;
-    let mut bytes = 0;
-    loop {
+    for shift in (0..u32::BITS).step_by(7) {
+        if !input.has_remaining() {
+            return Err(Error::TruncatedInput);
+        }
+        let c = input.get_u8();
+        // the fifth byte holds the top 4 bits, higher bits would be shifted out
+        if shift == 28 && c & 0x70 != 0 {
+            break;
+        }
+        val |= u32::from(c & 127) << shift;
+        if (c & 128) != 0 {
+            return Ok(val);
+        }
+    }
+    Err(Error::DecodingError {
+        source: std::io::ErrorKind::InvalidData.into(),
+    })
+}
+
+/// Decode a value, failing if `input` ends early or the value does not fit a `u64`
+pub fn decode_u64(mut input: impl Buf) -> Result<u64, Error> {
+    let mut val: u64 = 0;
+    for shift in (0..u64::BITS).step_by(7) {
+        if !input.has_remaining() {
+            return Err(Error::TruncatedInput);
+        }
         let c = input.get_u8();
-        val += u32::from(c & 127) << (bytes * 7);
+        let bits = u64::from(c & 127);
+        if (bits << shift) >> shift != bits {
+            break;
+        }
+        val |= bits << shift;
         if (c & 128) != 0 {
-            return val;
+            return Ok(val);
         }
-        bytes += 1;
     }
+    Err(Error::DecodingError {
+        source: std::io::ErrorKind::InvalidData.into(),
+    })
+}
+
+/// Encode a value of up to 64 bits, values fitting a `u32` are encoded as by [`encode`]
+#[allow(clippy::cast_possible_truncation)]
+pub fn encode_u64(mut output: impl BufMut, mut num: u64) -> usize {
+    let mut written = 1;
+    while num > 127 {
+        output.put_u8((num & 127) as u8);
+        num >>= 7;
+        written += 1;
+    }
+    output.put_u8(num as u8 | (1 << 7));
+    written
 }
 
 #[allow(clippy::identity_op, clippy::cast_possible_truncation)]
@@ -58,8 +104,42 @@ mod tests {
         fn encode_and_decode_single(num: u32)  {
             let mut buf = Vec::with_capacity(6);
             super::encode(&mut buf, num);
-            let decoded = super::decode(&buf[..]);
+            let decoded = super::decode(&buf[..])?;
             assert_eq!(decoded,num);
         }
     }
+
+    proptest! {
+        #[test]
+        fn encode_and_decode_u64(num: u64)  {
+            let mut buf = Vec::with_capacity(10);
+            let written = super::encode_u64(&mut buf, num);
+            assert_eq!(written, buf.len());
+            assert_eq!(super::decode_u64(&buf[..])?, num);
+            if let Ok(num) = u32::try_from(num) {
+                let mut compact = Vec::with_capacity(6);
+                super::encode(&mut compact, num);
+                assert_eq!(compact, buf);
+            }
+        }
+    }
+
+    #[test]
+    fn reject_overlong_fifth_byte() {
+        assert_eq!(
+            super::decode(&[0x7f, 0x7f, 0x7f, 0x7f, 0x8f][..]).unwrap(),
+            u32::MAX
+        );
+        for fifth in [0x90, 0xa0, 0xc0, 0xff, 0x10] {
+            let result = super::decode(&[0x7f, 0x7f, 0x7f, 0x7f, fifth][..]);
+            assert!(matches!(result, Err(crate::Error::DecodingError { .. })));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn decode_arbitrary(input: Vec<u8>)  {
+            let _ = super::decode(&input[..]);
+        }
+    }
 }